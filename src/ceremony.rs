@@ -0,0 +1,209 @@
+use ark_ec_old::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{One, PrimeField, Zero};
+use ark_marlin::rng::FiatShamirRng;
+use ark_poly_commit::kzg10;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{start_timer, end_timer, cfg_iter, UniformRand};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+use crate::Blake2s;
+use crate::SimpleHashFiatShamirRng;
+use crate::ChaChaRng;
+use crate::setup::check_srs;
+use crate::error::PackMarlinError;
+
+const CONTRIBUTION_DOMAIN: &[u8] = b"packmarlin-srs-contribution";
+
+/// Public record of one update of the SRS trapdoor from beta to s * beta.
+/// `s_g` and `s_h` carry s in both groups, `pok_*` is a Schnorr proof of knowledge of s for `s_g`.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Contribution<E: PairingEngine> {
+    pub prev_beta_g: E::G1Affine,
+    pub next_beta_g: E::G1Affine,
+    pub s_g: E::G1Affine,
+    pub s_h: E::G2Affine,
+    pub pok_commitment: E::G1Affine,
+    pub pok_response: E::Fr,
+}
+
+// Schnorr challenge, bound to the update it proves
+fn pok_challenge<E: PairingEngine>(
+    prev_beta_g: &E::G1Affine,
+    next_beta_g: &E::G1Affine,
+    s_g: &E::G1Affine,
+    s_h: &E::G2Affine,
+    pok_commitment: &E::G1Affine
+) -> Result<E::Fr, PackMarlinError> {
+    let mut fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(
+        &to_bytes![&CONTRIBUTION_DOMAIN, prev_beta_g, next_beta_g, s_g, s_h, pok_commitment]?
+    );
+
+    Ok(E::Fr::rand(&mut fs_rng))
+}
+
+// p_i * s^i for every i
+fn scale_powers<E: PairingEngine>(powers: &[E::G1Affine], powers_of_s: &[E::Fr]) -> Vec<E::G1Affine> {
+    let scaled = cfg_iter!(powers)
+        .zip(powers_of_s)
+        .map(|(p, s)| p.mul(s.into_repr()))
+        .collect::<Vec<E::G1Projective>>();
+
+    E::G1Projective::batch_normalization_into_affine(&scaled)
+}
+
+/// Re-randomizes the trapdoor of `srs` with a fresh secret s from `rng`, which is zeroized
+/// afterwards. Returns the updated SRS and the record that lets anyone check the update.
+pub fn contribute<E: PairingEngine, R: RngCore + CryptoRng>(
+    srs: &kzg10::UniversalParams<E>,
+    rng: &mut R
+) -> Result<(kzg10::UniversalParams<E>, Contribution<E>), PackMarlinError> {
+    let contribute_time = start_timer!(|| "Packmarlin::Contribute");
+    if !srs.neg_powers_of_h.is_empty() {
+        return Err(PackMarlinError::InvalidSrs("updating negative powers of h is not supported".into()));
+    }
+    if srs.powers_of_g.len() < 2 {
+        return Err(PackMarlinError::SrsTooSmall { needed: 1, available: srs.powers_of_g.len().saturating_sub(1) });
+    }
+
+    let mut s = E::Fr::rand(rng);
+
+    let num_powers = srs.powers_of_g.len().max(srs.powers_of_gamma_g.len());
+    let mut powers_of_s = Vec::with_capacity(num_powers);
+    let mut cur = E::Fr::one();
+    for _ in 0..num_powers {
+        powers_of_s.push(cur);
+        cur *= &s;
+    }
+    cur.zeroize();
+
+    let powers_of_g = scale_powers::<E>(&srs.powers_of_g, &powers_of_s);
+    let powers_of_gamma_g = srs.powers_of_gamma_g.values().cloned().collect::<Vec<E::G1Affine>>();
+    let powers_of_gamma_g = scale_powers::<E>(&powers_of_gamma_g, &powers_of_s)
+        .into_iter()
+        .enumerate()
+        .collect();
+    powers_of_s.zeroize();
+
+    let beta_h = srs.beta_h.mul(s.into_repr()).into_affine();
+    let s_g = srs.powers_of_g[0].mul(s.into_repr()).into_affine();
+    let s_h = srs.h.mul(s.into_repr()).into_affine();
+
+    let mut k = E::Fr::rand(rng);
+    let pok_commitment = srs.powers_of_g[0].mul(k.into_repr()).into_affine();
+    let c = pok_challenge::<E>(&srs.powers_of_g[1], &powers_of_g[1], &s_g, &s_h, &pok_commitment);
+    let pok_response = c.map(|c| k + c * s);
+
+    k.zeroize();
+    s.zeroize();
+    let pok_response = pok_response?;
+
+    let contribution = Contribution {
+        prev_beta_g: srs.powers_of_g[1],
+        next_beta_g: powers_of_g[1],
+        s_g,
+        s_h,
+        pok_commitment,
+        pok_response,
+    };
+
+    let updated = kzg10::UniversalParams {
+        powers_of_g,
+        powers_of_gamma_g,
+        h: srs.h,
+        beta_h,
+        neg_powers_of_h: srs.neg_powers_of_h.clone(),
+        prepared_h: srs.h.into(),
+        prepared_beta_h: beta_h.into(),
+    };
+
+    end_timer!(contribute_time);
+
+    Ok((updated, contribution))
+}
+
+// The update took beta*G to s*beta*G for the s in s_g and s_h, and its author knows s
+fn verify_contribution<E: PairingEngine>(
+    g: E::G1Affine,
+    h: E::G2Affine,
+    contribution: &Contribution<E>
+) -> Result<bool, PackMarlinError> {
+    let Contribution { prev_beta_g, next_beta_g, s_g, s_h, pok_commitment, pok_response } = contribution;
+
+    let c = pok_challenge::<E>(prev_beta_g, next_beta_g, s_g, s_h, pok_commitment)?;
+    let pok_valid = g.mul(pok_response.into_repr()) == pok_commitment.into_projective() + s_g.mul(c.into_repr());
+
+    let same_s = E::pairing(*s_g, h) == E::pairing(g, *s_h);
+    let updates_beta = E::pairing(*next_beta_g, h) == E::pairing(*prev_beta_g, *s_h);
+
+    Ok(pok_valid && same_s && updates_beta && !s_g.is_zero())
+}
+
+/// Checks that `current` was derived from `initial` by the given chain of contributions,
+/// in order, and that its powers are consistent
+pub fn verify_contributions<E: PairingEngine, R: RngCore>(
+    initial: &kzg10::UniversalParams<E>,
+    current: &kzg10::UniversalParams<E>,
+    contributions: &[Contribution<E>],
+    rng: &mut R
+) -> Result<bool, PackMarlinError> {
+    let verify_time = start_timer!(|| "Packmarlin::Verify_Contributions");
+    if initial.powers_of_g.len() < 2 || current.powers_of_g.len() < 2 {
+        return Err(PackMarlinError::InvalidSrs("an SRS without beta*G".into()));
+    }
+
+    let g = initial.powers_of_g[0];
+    let h = initial.h;
+    let same_generators = current.powers_of_g[0] == g && current.h == h
+        && current.powers_of_gamma_g.get(&0) == initial.powers_of_gamma_g.get(&0);
+
+    let mut beta_g = initial.powers_of_g[1];
+    let mut chain_valid = true;
+    for contribution in contributions {
+        chain_valid &= contribution.prev_beta_g == beta_g && verify_contribution(g, h, contribution)?;
+        beta_g = contribution.next_beta_g;
+    }
+    chain_valid &= current.powers_of_g[1] == beta_g;
+
+    let powers_valid = check_srs(current, rng);
+    end_timer!(verify_time);
+
+    Ok(same_generators && chain_valid && powers_valid)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::{Bls12_381, Fr};
+    use ark_std::test_rng;
+
+    use crate::setup::kzg_setup;
+
+    #[test]
+    fn verifies_a_chain_of_contributions() {
+        let rng = &mut test_rng();
+        let initial = kzg_setup::<Bls12_381, _>(8, false, rng);
+        let (first, c1) = contribute(&initial, rng).unwrap();
+        let (second, c2) = contribute(&first, rng).unwrap();
+
+        assert!(verify_contributions(&initial, &first, &[c1.clone()], rng).unwrap());
+        assert!(verify_contributions(&initial, &second, &[c1.clone(), c2.clone()], rng).unwrap());
+
+        // missing or reordered contributions do not lead to the current SRS
+        assert!(!verify_contributions(&initial, &second, &[c1.clone()], rng).unwrap());
+        assert!(!verify_contributions(&initial, &second, &[c2.clone(), c1.clone()], rng).unwrap());
+
+        let mut forged = c2;
+        forged.pok_response += Fr::one();
+        assert!(!verify_contributions(&initial, &second, &[c1, forged], rng).unwrap());
+    }
+
+    #[test]
+    fn refuses_to_update_negative_powers() {
+        let rng = &mut test_rng();
+        let srs = kzg_setup::<Bls12_381, _>(4, true, rng);
+
+        assert!(matches!(contribute(&srs, rng), Err(PackMarlinError::InvalidSrs(_))));
+    }
+}
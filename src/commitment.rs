@@ -0,0 +1,133 @@
+use ark_ec_old::{msm::VariableBaseMSM, AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::PrimeField;
+use ark_poly::GeneralEvaluationDomain;
+use ark_poly_commit::{kzg10, marlin_pc, ipa_pc, PolynomialCommitment};
+use ark_poly_commit::sonic_pc::SonicKZG10;
+use ark_poly_commit::ipa_pc::InnerProductArgPC;
+use rand::{CryptoRng, RngCore};
+use std::path::Path;
+
+use crate::Blake2s;
+use crate::DensePolynomial;
+use crate::MarlinKZG10;
+use crate::setup::{kzg_setup, lagrange_srs};
+use crate::error::PackMarlinError;
+
+/// Marlin-KZG10 commitments over `E`, the default backend
+pub type MarlinKZG<E> = MarlinKZG10<E,DensePolynomial<<E as PairingEngine>::Fr>>;
+
+/// Sonic-KZG10 commitments over `E`: the same trusted setup, smaller verifier keys
+pub type SonicKZG<E> = SonicKZG10<E,DensePolynomial<<E as PairingEngine>::Fr>>;
+
+/// Inner-product-argument commitments in G1 of `E`: no trusted setup, but proofs and
+/// verification grow logarithmically resp. linearly with the circuit
+pub type IpaPC<E> = InnerProductArgPC<<E as PairingEngine>::G1Affine,Blake2s,DensePolynomial<<E as PairingEngine>::Fr>>;
+
+/// Polynomial commitments PackMarlin can run on. A commitment must be linear in the coefficients,
+/// sum c_i * G_i over bases G_i of the committer key, so that the verifier can move the val
+/// commitments of the index by poso_rand with the location commitments alone.
+pub trait HomomorphicCommitment<F: PrimeField>:
+    PolynomialCommitment<F, DensePolynomial<F>, Error = ark_poly_commit::Error>
+{
+    /// Universal parameters for polynomials up to `max_degree`. Any trapdoor comes from `rng`
+    /// and is zeroized afterwards
+    fn universal_setup<R: RngCore + CryptoRng>(max_degree: usize, rng: &mut R) -> Result<Self::UniversalParams, PackMarlinError>;
+
+    /// Commitments to the Lagrange basis of `domain`, as `commit` computes them without hiding.
+    /// They are read from and written to `cache` if given, see `setup::lagrange_srs`
+    fn lagrange_commitments(
+        ck: &Self::CommitterKey,
+        domain: GeneralEvaluationDomain<F>,
+        cache: Option<&Path>
+    ) -> Result<Vec<Self::Commitment>, PackMarlinError>;
+
+    /// sum scalars_i * comms_i, for commitments without a degree bound
+    fn combine(comms: &[Self::Commitment], scalars: &[F]) -> Self::Commitment;
+
+    /// Whether `srs` has everything this backend takes from it when indexing
+    fn check_universal(_srs: &Self::UniversalParams) -> Result<(), PackMarlinError> {
+        Ok(())
+    }
+}
+
+fn msm<G: AffineCurve>(bases: &[G], scalars: &[G::ScalarField]) -> G {
+    let scalars = scalars.iter().map(|s| s.into_repr()).collect::<Vec<_>>();
+    VariableBaseMSM::multi_scalar_mul(bases, &scalars).into_affine()
+}
+
+impl<E: PairingEngine> HomomorphicCommitment<E::Fr> for MarlinKZG<E> {
+    fn universal_setup<R: RngCore + CryptoRng>(max_degree: usize, rng: &mut R) -> Result<Self::UniversalParams, PackMarlinError> {
+        Ok(kzg_setup::<E, _>(max_degree, false, rng))
+    }
+
+    fn lagrange_commitments(
+        ck: &Self::CommitterKey,
+        domain: GeneralEvaluationDomain<E::Fr>,
+        cache: Option<&Path>
+    ) -> Result<Vec<Self::Commitment>, PackMarlinError> {
+        Ok(lagrange_srs(&ck.powers, domain, cache)?
+            .into_iter()
+            .map(|p| marlin_pc::Commitment { comm: kzg10::Commitment(p), shifted_comm: None })
+            .collect())
+    }
+
+    fn combine(comms: &[Self::Commitment], scalars: &[E::Fr]) -> Self::Commitment {
+        let bases = comms.iter().map(|c| c.comm.0).collect::<Vec<_>>();
+        marlin_pc::Commitment { comm: kzg10::Commitment(msm(&bases, scalars)), shifted_comm: None }
+    }
+}
+
+impl<E: PairingEngine> HomomorphicCommitment<E::Fr> for SonicKZG<E> {
+    // degree bounds are enforced with the negative powers of h
+    fn universal_setup<R: RngCore + CryptoRng>(max_degree: usize, rng: &mut R) -> Result<Self::UniversalParams, PackMarlinError> {
+        Ok(kzg_setup::<E, _>(max_degree, true, rng))
+    }
+
+    fn lagrange_commitments(
+        ck: &Self::CommitterKey,
+        domain: GeneralEvaluationDomain<E::Fr>,
+        cache: Option<&Path>
+    ) -> Result<Vec<Self::Commitment>, PackMarlinError> {
+        Ok(lagrange_srs(&ck.powers_of_g, domain, cache)?
+            .into_iter()
+            .map(kzg10::Commitment)
+            .collect())
+    }
+
+    fn combine(comms: &[Self::Commitment], scalars: &[E::Fr]) -> Self::Commitment {
+        let bases = comms.iter().map(|c| c.0).collect::<Vec<_>>();
+        kzg10::Commitment(msm(&bases, scalars))
+    }
+
+    fn check_universal(srs: &Self::UniversalParams) -> Result<(), PackMarlinError> {
+        if srs.neg_powers_of_h.len() < srs.powers_of_g.len() {
+            return Err(PackMarlinError::InvalidSrs(
+                "Sonic-KZG10 needs a negative power of h for every power of G, e.g. from `PackMarlin::setup`".into()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<G: AffineCurve> HomomorphicCommitment<G::ScalarField> for InnerProductArgPC<G,Blake2s,DensePolynomial<G::ScalarField>> {
+    // the bases are hashed to the curve, so there is no trapdoor
+    fn universal_setup<R: RngCore + CryptoRng>(max_degree: usize, rng: &mut R) -> Result<Self::UniversalParams, PackMarlinError> {
+        Ok(Self::setup(max_degree, None, rng)?)
+    }
+
+    fn lagrange_commitments(
+        ck: &Self::CommitterKey,
+        domain: GeneralEvaluationDomain<G::ScalarField>,
+        cache: Option<&Path>
+    ) -> Result<Vec<Self::Commitment>, PackMarlinError> {
+        Ok(lagrange_srs(&ck.comm_key, domain, cache)?
+            .into_iter()
+            .map(|p| ipa_pc::Commitment { comm: p, shifted_comm: None })
+            .collect())
+    }
+
+    fn combine(comms: &[Self::Commitment], scalars: &[G::ScalarField]) -> Self::Commitment {
+        let bases = comms.iter().map(|c| c.comm).collect::<Vec<_>>();
+        ipa_pc::Commitment { comm: msm(&bases, scalars), shifted_comm: None }
+    }
+}
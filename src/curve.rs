@@ -0,0 +1,146 @@
+use ark_bls12_381_old::{Fq2 as BlsFq2, G1Affine as BlsG1Affine, G2Affine as BlsG2Affine};
+use ark_bn254_old::{Fq2 as BnFq2, G1Affine as BnG1Affine, G2Affine as BnG2Affine};
+use ark_ec_old::PairingEngine;
+use ark_ff::{BigInteger, FpParameters, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::{Bls12_381, Bn254};
+use crate::ptau::{read_sections, read_u32, seek_section};
+use crate::error::PackMarlinError;
+
+/// Pairing engines PackMarlin ships with. Adds what `PairingEngine` leaves to the concrete
+/// curve: building points from raw coordinates (ceremony files) and checking decoded points.
+pub trait PackCurve: PairingEngine {
+    /// Name as Circom's `--prime` spells it
+    const NAME: &'static str;
+
+    /// Circuit field this engine proves, which tags the keys and proofs made with it
+    const FIELD: CircuitField;
+
+    /// Point with the given coordinates, `None` unless on the curve and in the prime-order subgroup
+    fn g1_from_xy(x: Self::Fq, y: Self::Fq) -> Option<Self::G1Affine>;
+
+    /// As `g1_from_xy`, with the Fq2 coordinates given as (c0, c1)
+    fn g2_from_xy(x: [Self::Fq; 2], y: [Self::Fq; 2]) -> Option<Self::G2Affine>;
+
+    /// Whether a point decoded without checks is on the curve and in the prime-order subgroup
+    fn g1_is_valid(p: &Self::G1Affine) -> bool;
+
+    fn g2_is_valid(p: &Self::G2Affine) -> bool;
+}
+
+macro_rules! impl_pack_curve {
+    ($engine:ty, $name:expr, $field:expr, $g1:ty, $g2:ty, $fq2:ty) => {
+        impl PackCurve for $engine {
+            const NAME: &'static str = $name;
+            const FIELD: CircuitField = $field;
+
+            fn g1_from_xy(x: Self::Fq, y: Self::Fq) -> Option<Self::G1Affine> {
+                let p = <$g1>::new(x, y, false);
+                Self::g1_is_valid(&p).then_some(p)
+            }
+
+            fn g2_from_xy(x: [Self::Fq; 2], y: [Self::Fq; 2]) -> Option<Self::G2Affine> {
+                let p = <$g2>::new(<$fq2>::new(x[0], x[1]), <$fq2>::new(y[0], y[1]), false);
+                Self::g2_is_valid(&p).then_some(p)
+            }
+
+            fn g1_is_valid(p: &Self::G1Affine) -> bool {
+                p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve()
+            }
+
+            fn g2_is_valid(p: &Self::G2Affine) -> bool {
+                p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve()
+            }
+        }
+    };
+}
+
+impl_pack_curve!(Bn254, "bn128", CircuitField::Bn254, BnG1Affine, BnG2Affine, BnFq2);
+impl_pack_curve!(Bls12_381, "bls12381", CircuitField::Bls12_381, BlsG1Affine, BlsG2Affine, BlsFq2);
+
+/// Scalar field of a Circom circuit, which decides the pairing engine to prove it with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitField {
+    Bn254,
+    Bls12_381,
+}
+
+impl CanonicalSerialize for CircuitField {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        (*self as u8).serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1
+    }
+}
+
+impl CanonicalDeserialize for CircuitField {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        match u8::deserialize(reader)? {
+            0 => Ok(CircuitField::Bn254),
+            1 => Ok(CircuitField::Bls12_381),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+// .r1cs header section: field size n8 u32 | prime (n8 bytes, little-endian) | counts
+const R1CS_MAGIC: &[u8; 4] = b"r1cs";
+const R1CS_HEADER_SECTION: u32 = 1;
+
+fn is_modulus<E: PairingEngine>(prime: &[u8]) -> bool {
+    <E::Fr as PrimeField>::Params::MODULUS.to_bytes_le() == prime
+}
+
+/// Reads the prime from the header of a Circom `.r1cs` file
+pub fn r1cs_field(path: &Path) -> Result<CircuitField, PackMarlinError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let sections = read_sections(&mut reader, R1CS_MAGIC)?;
+
+    seek_section(&mut reader, &sections, R1CS_HEADER_SECTION)?;
+    let n8 = read_u32(&mut reader)? as usize;
+    if n8 > 64 {
+        return Err(PackMarlinError::Parse(format!("{}: field of {} bytes", path.display(), n8)));
+    }
+    let mut prime = vec![0u8; n8];
+    reader.read_exact(&mut prime)?;
+
+    if is_modulus::<Bn254>(&prime) {
+        Ok(CircuitField::Bn254)
+    } else if is_modulus::<Bls12_381>(&prime) {
+        Ok(CircuitField::Bls12_381)
+    } else {
+        Err(PackMarlinError::Parse(format!(
+            "{}: prime is neither {} nor {}", path.display(), Bn254::NAME, Bls12_381::NAME
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{sections_file, temp_file};
+
+    fn r1cs_header(name: &str, prime: Vec<u8>) -> std::path::PathBuf {
+        let mut header = (prime.len() as u32).to_le_bytes().to_vec();
+        header.extend(prime);
+        temp_file(name, &sections_file(R1CS_MAGIC, &[(R1CS_HEADER_SECTION, header)]))
+    }
+
+    #[test]
+    fn detects_the_circuit_field() {
+        let bn = r1cs_header("bn.r1cs", <<Bn254 as PairingEngine>::Fr as PrimeField>::Params::MODULUS.to_bytes_le());
+        let bls = r1cs_header("bls.r1cs", <<Bls12_381 as PairingEngine>::Fr as PrimeField>::Params::MODULUS.to_bytes_le());
+        let other = r1cs_header("other.r1cs", <<Bls12_381 as PairingEngine>::Fq as PrimeField>::Params::MODULUS.to_bytes_le());
+
+        assert_eq!(r1cs_field(&bn).unwrap(), CircuitField::Bn254);
+        assert_eq!(r1cs_field(&bls).unwrap(), CircuitField::Bls12_381);
+        assert!(matches!(r1cs_field(&other), Err(PackMarlinError::Parse(_))));
+    }
+}
@@ -0,0 +1,64 @@
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::SerializationError;
+use std::fmt::Debug;
+use thiserror::Error;
+
+/// Everything that can go wrong in the PackMarlin pipeline
+#[derive(Debug, Error)]
+pub enum PackMarlinError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("malformed artifact: {0}")]
+    Serialization(#[from] SerializationError),
+    #[error("cannot parse input: {0}")]
+    Parse(String),
+    #[error("invalid poso params: {0}")]
+    InvalidParams(&'static str),
+    #[error("witness does not match the subcircuit: {0}")]
+    WitnessMismatch(String),
+    #[error("witness generation failed: {0}")]
+    WitnessGeneration(String),
+    #[error("witness does not satisfy the constraints")]
+    Unsatisfied,
+    #[error("SRS supports degree at most {available}, but {needed} is needed")]
+    SrsTooSmall { needed: usize, available: usize },
+    #[error("invalid SRS: {0}")]
+    InvalidSrs(String),
+    #[error("poso layout does not match the index: {0}")]
+    LayoutMismatch(String),
+    /// Failures inside Marlin, the polynomial commitment or constraint synthesis
+    #[error("Marlin error: {0}")]
+    Marlin(String),
+    #[error("proof rejected")]
+    ProofRejected,
+}
+
+impl From<SynthesisError> for PackMarlinError {
+    fn from(e: SynthesisError) -> Self {
+        PackMarlinError::Marlin(e.to_string())
+    }
+}
+
+impl From<ark_poly_commit::Error> for PackMarlinError {
+    fn from(e: ark_poly_commit::Error) -> Self {
+        PackMarlinError::Marlin(e.to_string())
+    }
+}
+
+impl<E: Debug> From<ark_marlin::Error<E>> for PackMarlinError {
+    fn from(e: ark_marlin::Error<E>) -> Self {
+        PackMarlinError::Marlin(format!("{:?}", e))
+    }
+}
+
+impl From<ark_marlin::ahp::Error> for PackMarlinError {
+    fn from(e: ark_marlin::ahp::Error) -> Self {
+        PackMarlinError::Marlin(format!("{:?}", e))
+    }
+}
+
+impl From<serde_json::Error> for PackMarlinError {
+    fn from(e: serde_json::Error) -> Self {
+        PackMarlinError::Parse(e.to_string())
+    }
+}
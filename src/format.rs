@@ -0,0 +1,98 @@
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use std::fs::File;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::curve::{CircuitField, PackCurve};
+use crate::error::PackMarlinError;
+
+/// Current format version of a serialized `PackMarlinProof`
+pub const PROOF_VERSION: u32 = 1;
+
+/// Current format version of a serialized `PackMarlinVerifierKey`
+pub const VK_VERSION: u32 = 1;
+
+/// Current format version of a serialized `ProverKey`
+pub const PK_VERSION: u32 = 1;
+
+/// Format version tag written ahead of an artifact; loading fails unless it reads back `V`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Version<const V: u32>;
+
+impl<const V: u32> CanonicalSerialize for Version<V> {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        V.serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        V.serialized_size()
+    }
+}
+
+impl<const V: u32> CanonicalDeserialize for Version<V> {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        if u32::deserialize(reader)? != V {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(Version)
+    }
+}
+
+/// Curve tag written after the version of an artifact; loading fails unless it names `E`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CurveTag<E: PackCurve>(PhantomData<E>);
+
+impl<E: PackCurve> CurveTag<E> {
+    pub(crate) fn new() -> Self {
+        CurveTag(PhantomData)
+    }
+}
+
+impl<E: PackCurve> CanonicalSerialize for CurveTag<E> {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        E::FIELD.serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        E::FIELD.serialized_size()
+    }
+}
+
+impl<E: PackCurve> CanonicalDeserialize for CurveTag<E> {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        if CircuitField::deserialize(reader)? != E::FIELD {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(CurveTag::new())
+    }
+}
+
+/// Curve a prover key, verifier key or proof was made for, read from the tag after its version
+pub fn artifact_field(path: &Path) -> Result<CircuitField, PackMarlinError> {
+    let mut header = [0u8; 5];
+    File::open(path)?.read_exact(&mut header)?;
+
+    Ok(CircuitField::deserialize(&header[4..])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bls12_381, Bn254};
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn artifacts_only_load_on_their_curve() {
+        let mut bytes = vec![];
+        (Version::<VK_VERSION>, CurveTag::<Bn254>::new()).serialize(&mut bytes).unwrap();
+
+        assert!(<(Version<VK_VERSION>, CurveTag<Bn254>)>::deserialize(&bytes[..]).is_ok());
+        assert!(<(Version<VK_VERSION>, CurveTag<Bls12_381>)>::deserialize(&bytes[..]).is_err());
+        assert!(<(Version<{ VK_VERSION + 1 }>, CurveTag<Bn254>)>::deserialize(&bytes[..]).is_err());
+
+        let path = temp_file("tagged.vk", &bytes);
+        assert_eq!(artifact_field(&path).unwrap(), CircuitField::Bn254);
+    }
+}
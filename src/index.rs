@@ -1,115 +1,208 @@
+use ark_ff::PrimeField;
 use ark_marlin::{ IndexProverKey, IndexVerifierKey};
-use ark_poly_commit::marlin_pc::CommitterKey;
+use ark_marlin::ahp::AHPForR1CS;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 use ark_std::{ start_timer, end_timer };
-use std::process::Command;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 use crate::Blake2s;
 use crate::SimpleHashFiatShamirRng;
-use crate::DensePolynomial;
 use crate::ChaChaRng;
-use crate::MarlinKZG10;
 use crate::Marlin;
-use crate::{ Bls12_381, BlsFr , G1Affine};
 use crate::UniversalSRS;
-use crate::{ R1CSFile, R1CS, CircomCircuit };
-use crate::{ BufReader, Cursor, read, read_to_string, FromStr };
+use crate::layout::{diff_support, locate, IndexedCoeff, Matrix};
+use crate::params::PosoParams;
+use crate::curve::PackCurve;
+use crate::format::{CurveTag, Version, PK_VERSION, VK_VERSION};
+use crate::setup::{eval_domain, IndexSizes};
+use crate::commitment::{HomomorphicCommitment, MarlinKZG};
+use crate::{ CircomCircuit, R1CS };
+use crate::packer::{self, Subcircuit};
+use crate::error::PackMarlinError;
+
+
+/// Position of the val polynomial of `matrix` in `IndexVerifierKey::index_comms`
+pub(crate) fn val_index<F: PrimeField>(matrix: Matrix) -> usize {
+    AHPForR1CS::<F>::INDEXER_POLYNOMIALS
+        .iter()
+        .position(|label| *label == matrix.val_label())
+        .unwrap()
+}
 
+/// Commitment to the part of a val polynomial that the coefficients of one slot contribute:
+/// the Lagrange basis over K at their joint positions, each scaled by the val evaluation there
+/// (the coefficient times the inverse of eq_poly at its column), tagged with the slot
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct LocComm<F: PrimeField, PC: HomomorphicCommitment<F>> {
+    pub slot: usize,
+    pub matrix: Matrix,
+    pub comm: PC::Commitment,
+}
 
-pub(crate) fn loc_comm(_circuit: CircomCircuit<Bls12_381>, ck: CommitterKey<Bls12_381>) -> Vec<G1Affine> {
+/// Location commitments of all randomized coefficients, one per slot and matrix
+pub(crate) fn loc_comm<F: PrimeField, PC: HomomorphicCommitment<F>>(
+    pk: &IndexProverKey<F,PC>,
+    coefficients: &[IndexedCoeff],
+    lagrange_cache: Option<&Path>
+) -> Result<Vec<LocComm<F, PC>>, PackMarlinError> {
     let rand_commitments_time = start_timer!(|| "Packmarlin::Commitments to locations of randomness");
 
-    // let offset = 100; // offset for location of randomness
-    // let poso_size = 10000;
-    // let num_poso = 8;
-    // compute commitments and return them
-    // let mut onehot_list = vec![vec![BlsFr::from(0); 80000]; poso_size];
-
-    // let domain = GeneralEvaluationDomain::new(80000).unwrap();
-
-    // for i in 0..poso_size {
-    //     for j in 0..num_poso {
-    //         onehot_list[i][offset + i + j*num_poso] = BlsFr::from(1);
-    //     }
-    // }
+    let domain_k = eval_domain::<F>(pk.index.index_info.num_non_zero)?;
+    let lagrange = PC::lagrange_commitments(&pk.committer_key, domain_k, lagrange_cache)?;
 
-    // let mut onehot_interpolated = vec![];
-    // let mut onehot_labeled = vec![];
-    // for i in 0..poso_size {
-    //     onehot_interpolated.push(EvaluationsOnDomain::from_vec_and_domain(onehot_list[i].clone(), domain).interpolate());
-    //     onehot_labeled.push(LabeledPolynomial::new("w".to_string(), onehot_interpolated[i].clone(), None, None));
-    // }
+    let evals_on_k = &pk.index.joint_arith.evals_on_K;
+    let mut per_slot: BTreeMap<(usize, Matrix), (Vec<PC::Commitment>, Vec<F>)> = BTreeMap::new();
+    for coeff in coefficients {
+        let val = match coeff.matrix {
+            Matrix::A => &evals_on_k.val_a,
+            Matrix::B => &evals_on_k.val_b,
+            Matrix::C => &evals_on_k.val_c,
+        };
 
-    // let (onehot_comm, _) = 
-    //     MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&ck, &onehot_labeled, None).unwrap();
+        let (bases, scalars) = per_slot.entry((coeff.slot, coeff.matrix)).or_default();
+        bases.push(lagrange[coeff.pos].clone());
+        scalars.push(val.evals[coeff.pos]);
+    }
 
-    let onehot_comm = ck.clone().powers;
+    let loc_comm = per_slot
+        .into_iter()
+        .map(|((slot, matrix), (bases, scalars))| LocComm { slot, matrix, comm: PC::combine(&bases, &scalars) })
+        .collect();
 
     end_timer!(rand_commitments_time);
 
-    onehot_comm
+    Ok(loc_comm)
 }
 
-pub(crate) fn index(
-    srs: &UniversalSRS<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>
-) -> (IndexProverKey<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>, 
-      IndexVerifierKey<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>, 
-      Vec<G1Affine>)
-{
-    let s_index = start_timer!(|| "Packmarlin::Index");
+/// Marlin prover key of the packed circuit with its randomized coefficients,
+/// and the subcircuit needed to pack instance witnesses.
+/// Serializes with a leading format version and curve tag.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ProverKey<E: PackCurve, PC: HomomorphicCommitment<E::Fr> = MarlinKZG<E>> {
+    pub(crate) version: Version<PK_VERSION>,
+    pub(crate) curve: CurveTag<E>,
+    pub(crate) index_pk: IndexProverKey<E::Fr,PC>,
+    pub(crate) subcircuit: Subcircuit<E>,
+    pub(crate) coefficients: Vec<IndexedCoeff>,
+    pub(crate) params: PosoParams,
+}
 
-    let packer_time = start_timer!(|| "Running packer.js");
-    let _ = Command::new("node")
-        .arg("./packR1CS/scripts/packer.js")
-        .arg("norand")
-        .output()
-        .expect("packer.js failed");
-    end_timer!(packer_time);
+/// Everything a verifier needs: the Marlin vk of the original index, which carries the
+/// few SRS elements the KZG checks use, the location commitments that move it by poso_rand
+/// and the positions of K they may move.
+/// Serializes with a leading format version and curve tag, independently of the prover key.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PackMarlinVerifierKey<E: PackCurve, PC: HomomorphicCommitment<E::Fr> = MarlinKZG<E>> {
+    pub(crate) version: Version<VK_VERSION>,
+    pub(crate) curve: CurveTag<E>,
+    pub(crate) index_vk: IndexVerifierKey<E::Fr,PC>,
+    pub(crate) loc_comm: Vec<LocComm<E::Fr, PC>>,
+    pub(crate) diff_support: (usize, usize),
+    pub(crate) num_public: usize,
+    pub(crate) params: PosoParams,
+}
 
-    let file_time = start_timer!(|| "Loading R1CS and witness files");
-    let file: String = "./packR1CS/scripts/.output/".to_string();
+pub type VerifierKey<E, PC = MarlinKZG<E>> = PackMarlinVerifierKey<E, PC>;
 
-    let data = read(file.clone()+"packed_subcircuit.r1cs").unwrap();
-    let witness = read_to_string(file.clone()+"packed_witness.json").unwrap();
+impl<E: PackCurve, PC: HomomorphicCommitment<E::Fr>> ProverKey<E, PC> {
+    pub fn params(&self) -> PosoParams {
+        self.params
+    }
 
-    let reader = BufReader::new(Cursor::new(&data[..]));
-    let r1csfile = R1CSFile::<Bls12_381>::new(reader).unwrap();
-    let r1cs = R1CS::from(r1csfile);
+    /// Wires of one instance witness, the constant one included
+    pub fn num_variables(&self) -> usize {
+        self.subcircuit.num_variables
+    }
 
-    let witness: Vec<String> = serde_json::from_str(&witness).unwrap();
+    /// Public signals of one full instance witness
+    pub fn public_inputs(&self, witness: &[E::Fr]) -> Result<Vec<E::Fr>, PackMarlinError> {
+        if witness.len() != self.subcircuit.num_variables {
+            return Err(PackMarlinError::WitnessMismatch(
+                format!("{} wires instead of {}", witness.len(), self.subcircuit.num_variables)
+            ));
+        }
 
-    // convert witness into field elements
-    let witness = witness
-        .iter()
-        .map(|w| {
-            BlsFr::from_str(&w).unwrap()
-        })
-        .collect::<Vec<BlsFr>>();
+        Ok(witness[1..self.subcircuit.num_inputs].to_vec())
+    }
+}
+
+impl<E: PackCurve, PC: HomomorphicCommitment<E::Fr>> PackMarlinVerifierKey<E, PC> {
+    pub fn version(&self) -> u32 {
+        VK_VERSION
+    }
+
+    pub fn params(&self) -> PosoParams {
+        self.params
+    }
+
+    /// Public signals of one instance
+    pub fn num_public(&self) -> usize {
+        self.num_public
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.index_vk.index_info.num_constraints
+    }
+
+    pub fn num_variables(&self) -> usize {
+        self.index_vk.index_info.num_variables
+    }
+
+    pub fn num_non_zero(&self) -> usize {
+        self.index_vk.index_info.num_non_zero
+    }
+
+    /// Exact sizes of the index, to trim an SRS down to what re-indexing this circuit needs
+    pub fn index_sizes(&self) -> IndexSizes {
+        IndexSizes {
+            num_constraints: self.num_constraints(),
+            num_variables: self.num_variables(),
+            num_non_zero: self.num_non_zero(),
+        }
+    }
+}
 
-    let witness = Some(witness);
+pub(crate) fn index<E: PackCurve, PC: HomomorphicCommitment<E::Fr>>(
+    srs: &UniversalSRS<E::Fr,PC>,
+    subcircuit: &R1CS<E>,
+    params: PosoParams,
+    lagrange_cache: Option<&Path>
+) -> Result<(ProverKey<E, PC>, VerifierKey<E, PC>), PackMarlinError>
+{
+    let s_index = start_timer!(|| "Packmarlin::Index");
 
-    let gen_time = start_timer!(|| "Generating constraints");
-    let mut circuit = CircomCircuit::<Bls12_381>{r1cs, witness};
-    // let cs = ConstraintSystem::<BlsFr>::new_ref();
-    circuit.r1cs.wire_mapping = None;
-    // circuit.clone().generate_constraints(cs.clone()).unwrap();
+    PC::check_universal(srs)?;
 
-    // assert!(cs.is_satisfied().unwrap(), "Unsatisfied constraint system");
+    let subcircuit = Subcircuit::from(subcircuit);
+    let num_public = subcircuit.num_inputs - 1;
+    let packed = packer::pack(&subcircuit, &params, None)?;
+    if packed.layout.num_slots != params.num_challenges() {
+        return Err(PackMarlinError::LayoutMismatch(
+            format!("{} slots for {} poso challenges", packed.layout.num_slots, params.num_challenges())
+        ));
+    }
 
-    end_timer!(gen_time);
+    let num_inputs = packed.r1cs.num_inputs;
+    let circuit = CircomCircuit::<E>{ r1cs: packed.r1cs, witness: None };
 
-    end_timer!(file_time);
-    
     let (pk, vk) = Marlin::<
-        BlsFr,
-        MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
+        E::Fr,
+        PC,
         SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-    >::index(&srs, circuit.clone())
-    .unwrap();
+    >::index(&srs, circuit)?;
 
-    let loc_comm = loc_comm(circuit.clone(), pk.committer_key.clone());
+    // the indexer pads the public input to a domain, which shifts the private columns
+    let padded_inputs = eval_domain::<E::Fr>(num_inputs)?.size();
+    let index = &pk.index;
+    let coefficients = locate(&packed.layout, [&index.a[..], &index.b[..], &index.c[..]], num_inputs, padded_inputs)?;
+    let loc_comm = loc_comm(&pk, &coefficients, lagrange_cache)?;
+    let diff_support = diff_support(&coefficients);
 
     end_timer!(s_index);
 
-    // return loc_comm output as well
-    (pk, vk, loc_comm)
-} 
+    let pk = ProverKey { version: Version, curve: CurveTag::new(), index_pk: pk, subcircuit, coefficients, params };
+    let vk = PackMarlinVerifierKey { version: Version, curve: CurveTag::new(), index_vk: vk, loc_comm, diff_support, num_public, params };
+
+    Ok((pk, vk))
+}
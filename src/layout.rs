@@ -0,0 +1,219 @@
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use std::collections::BTreeSet;
+
+use crate::error::PackMarlinError;
+
+/// R1CS matrix holding a randomized coefficient
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Matrix {
+    A,
+    B,
+    C,
+}
+
+impl Matrix {
+    pub(crate) const ALL: [Matrix; 3] = [Matrix::A, Matrix::B, Matrix::C];
+
+    /// Label of the matching val polynomial in the Marlin index
+    pub(crate) fn val_label(&self) -> &'static str {
+        match self {
+            Matrix::A => "val_a",
+            Matrix::B => "val_b",
+            Matrix::C => "val_c",
+        }
+    }
+}
+
+impl CanonicalSerialize for Matrix {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        (*self as u8).serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1
+    }
+}
+
+impl CanonicalDeserialize for Matrix {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        match u8::deserialize(reader)? {
+            0 => Ok(Matrix::A),
+            1 => Ok(Matrix::B),
+            2 => Ok(Matrix::C),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+/// Matrix coefficient that the randomness of `slot` multiplies
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct RandCoeff {
+    pub slot: usize,
+    pub matrix: Matrix,
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Where poso randomness enters a packed circuit, as emitted by the packer.
+/// Slot `s` receives `poso_rand[s]`, which multiplies all of its coefficients.
+/// The packer derives it together with the packed circuit, so there is no metadata file to load,
+/// and the witness wires follow from the packed wire layout.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct PosoLayout {
+    pub num_slots: usize,
+    pub coefficients: Vec<RandCoeff>,
+}
+
+/// Randomized coefficient located in the index: its Marlin column and its position among
+/// the joint non-zeros of A, B and C, which is its index in the val evaluations over K
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct IndexedCoeff {
+    pub slot: usize,
+    pub matrix: Matrix,
+    pub row: usize,
+    pub col: usize,
+    pub pos: usize,
+}
+
+/// Locates every coefficient of `layout` in the matrices `[a, b, c]` of a Marlin index.
+/// The joint matrix holds, row by row, the union of the columns of a, b and c in ascending order,
+/// and all three val polynomials are evaluated over its non-zeros. Packed wires are mapped to
+/// Marlin columns, where the `num_inputs` public wires are padded to `padded_inputs`.
+pub(crate) fn locate<F>(
+    layout: &PosoLayout,
+    matrices: [&[Vec<(F, usize)>]; 3],
+    num_inputs: usize,
+    padded_inputs: usize
+) -> Result<Vec<IndexedCoeff>, PackMarlinError> {
+    let num_rows = matrices.iter().map(|m| m.len()).max().unwrap_or(0);
+
+    // sorted columns of every joint row and the position of its first non-zero
+    let mut offset = 0;
+    let joint = (0..num_rows)
+        .map(|r| {
+            let cols = matrices
+                .iter()
+                .filter_map(|m| m.get(r))
+                .flat_map(|row| row.iter().map(|(_, col)| *col))
+                .collect::<BTreeSet<usize>>()
+                .into_iter()
+                .collect::<Vec<usize>>();
+            let start = offset;
+            offset += cols.len();
+            (start, cols)
+        })
+        .collect::<Vec<_>>();
+
+    let mut located = BTreeSet::new();
+    layout.coefficients
+        .iter()
+        .map(|coeff| {
+            let mismatch = || PackMarlinError::LayoutMismatch(
+                format!("coefficient ({}, {}) of {:?} is not a non-zero of the index", coeff.row, coeff.col, coeff.matrix)
+            );
+
+            // the prover fixes poso_rand with the first Marlin round, which reads A and B
+            if coeff.matrix != Matrix::C {
+                return Err(PackMarlinError::LayoutMismatch(
+                    format!("coefficient ({}, {}) of {:?}: only C can be randomized", coeff.row, coeff.col, coeff.matrix)
+                ));
+            }
+
+            let col = if coeff.col < num_inputs { coeff.col } else { coeff.col - num_inputs + padded_inputs };
+            let in_matrix = matrices[coeff.matrix as usize]
+                .get(coeff.row)
+                .map_or(false, |row| row.iter().any(|(_, c)| *c == col));
+            if !in_matrix {
+                return Err(mismatch());
+            }
+
+            let (start, cols) = &joint[coeff.row];
+            let pos = start + cols.binary_search(&col).map_err(|_| mismatch())?;
+            // poso_rand scales the whole entry, so each one may only be randomized once
+            if !located.insert((coeff.matrix, pos)) {
+                return Err(PackMarlinError::LayoutMismatch(
+                    format!("coefficient ({}, {}) of {:?} is randomized twice", coeff.row, coeff.col, coeff.matrix)
+                ));
+            }
+
+            Ok(IndexedCoeff { slot: coeff.slot, matrix: coeff.matrix, row: coeff.row, col, pos })
+        })
+        .collect()
+}
+
+/// Joint positions [lo, hi) spanning all randomized coefficients. Randomizing leaves
+/// the val evaluations over K unchanged outside of them
+pub(crate) fn diff_support(coefficients: &[IndexedCoeff]) -> (usize, usize) {
+    let lo = coefficients.iter().map(|c| c.pos).min().unwrap_or(0);
+    let hi = coefficients.iter().map(|c| c.pos + 1).max().unwrap_or(0);
+
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Rows = Vec<Vec<(u64, usize)>>;
+
+    fn coeff(slot: usize, matrix: Matrix, row: usize, col: usize) -> RandCoeff {
+        RandCoeff { slot, matrix, row, col }
+    }
+
+    #[test]
+    fn locates_coefficients_among_the_joint_non_zeros() {
+        // joint rows {0, 2} and {1, 3}
+        let a: Rows = vec![vec![(1, 2)], vec![]];
+        let b: Rows = vec![vec![(1, 0)], vec![(1, 1)]];
+        let c: Rows = vec![vec![(1, 2), (1, 0)], vec![(1, 3), (1, 1)]];
+        let layout = PosoLayout {
+            num_slots: 2,
+            coefficients: vec![coeff(0, Matrix::C, 0, 2), coeff(1, Matrix::C, 1, 3)],
+        };
+
+        let located = locate(&layout, [&a[..], &b[..], &c[..]], 4, 4).unwrap();
+        assert_eq!(located.iter().map(|c| c.pos).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(diff_support(&located), (1, 4));
+    }
+
+    #[test]
+    fn rejects_randomizing_a_or_b() {
+        let a: Rows = vec![vec![(1, 1)]];
+        let c: Rows = vec![vec![(1, 0)]];
+
+        let layout = PosoLayout { num_slots: 1, coefficients: vec![coeff(0, Matrix::A, 0, 1)] };
+        assert!(matches!(
+            locate(&layout, [&a[..], &a[..], &c[..]], 1, 1),
+            Err(PackMarlinError::LayoutMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn maps_private_wires_past_the_padded_inputs() {
+        // 3 public wires padded to 4, so wire 3 is column 4
+        let empty: Rows = vec![];
+        let c: Rows = vec![vec![(1, 0), (1, 4)]];
+        let layout = PosoLayout { num_slots: 1, coefficients: vec![coeff(0, Matrix::C, 0, 3)] };
+
+        let located = locate(&layout, [&empty[..], &empty[..], &c[..]], 3, 4).unwrap();
+        assert_eq!((located[0].col, located[0].pos), (4, 1));
+    }
+
+    #[test]
+    fn rejects_missing_or_repeated_coefficients() {
+        let empty: Rows = vec![];
+        let c: Rows = vec![vec![(1, 0)]];
+
+        let missing = PosoLayout { num_slots: 1, coefficients: vec![coeff(0, Matrix::C, 0, 1)] };
+        assert!(matches!(
+            locate(&missing, [&empty[..], &empty[..], &c[..]], 1, 1),
+            Err(PackMarlinError::LayoutMismatch(_))
+        ));
+
+        let repeated = PosoLayout { num_slots: 2, coefficients: vec![coeff(0, Matrix::C, 0, 0), coeff(1, Matrix::C, 0, 0)] };
+        assert!(matches!(
+            locate(&repeated, [&empty[..], &empty[..], &c[..]], 1, 1),
+            Err(PackMarlinError::LayoutMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use ark_circom::{circom::{R1CSFile, R1CS}, CircomCircuit, WitnessCalculator};
+use ark_bls12_381_old::Bls12_381;
+use ark_bn254_old::Bn254;
+use ark_ec_old::PairingEngine;
+use ark_std::io::{BufReader, Cursor};
+use std::fs::read;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+use rand::{CryptoRng, RngCore};
+use ark_marlin::Marlin;
+use ark_poly_commit::marlin_pc::MarlinKZG10;
+use ark_poly::univariate::DensePolynomial;
+use blake2::Blake2s;
+use rand_chacha::ChaChaRng;
+use ark_marlin::SimpleHashFiatShamirRng;
+use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
+use ark_marlin::UniversalSRS;
+use ark_poly_commit::kzg10;
+use std::marker::PhantomData;
+use std::path::Path;
+
+#[macro_use(to_bytes)]
+extern crate ark_ff;
+
+mod error;
+mod curve;
+mod commitment;
+mod format;
+mod setup;
+mod srs;
+mod ptau;
+mod ceremony;
+mod layout;
+mod params;
+mod soundness;
+mod packer;
+mod witness;
+mod index;
+mod prove;
+mod verify;
+
+#[cfg(test)]
+mod test_utils;
+
+pub use error::PackMarlinError;
+pub use curve::{r1cs_field, CircuitField, PackCurve};
+pub use format::artifact_field;
+pub use commitment::{HomomorphicCommitment, IpaPC, MarlinKZG, SonicKZG};
+pub use index::{PackMarlinVerifierKey, ProverKey, VerifierKey};
+pub use params::PosoParams;
+pub use setup::IndexSizes;
+pub use prove::PackMarlinProof;
+pub use packer::read_subcircuit;
+pub use srs::{load_srs, SrsValidation};
+pub use ptau::import_ptau;
+pub use ceremony::{contribute, verify_contributions, Contribution};
+pub use witness::{calculate_witnesses, read_json, read_witnesses, read_wtns};
+pub use soundness::{minimal_params, security_bits, soundness_error_log2};
+
+/// Universal SRS of Marlin over `E` with `PC` commitments, Marlin-KZG10 by default
+pub type UniversalParams<E, PC = MarlinKZG<E>> = UniversalSRS<<E as PairingEngine>::Fr,PC>;
+
+/// PackMarlin over the pairing engine `E`: proves many instances of one Circom subcircuit at once.
+/// `E` must match the prime the circuit was compiled for, see `r1cs_field`.
+/// `PC` commits to the polynomials: the KZG backends need a trusted setup, `IpaPC` does not
+/// but has larger proofs and slower verification
+pub struct PackMarlin<E: PackCurve, PC: HomomorphicCommitment<E::Fr> = MarlinKZG<E>>(PhantomData<(E, PC)>);
+
+/// PackMarlin for circuits compiled with Circom's default prime
+pub type PackMarlinBn254 = PackMarlin<Bn254>;
+
+/// PackMarlin for circuits compiled with `--prime bls12381`
+pub type PackMarlinBls12_381 = PackMarlin<Bls12_381>;
+
+impl<E: PackCurve, PC: HomomorphicCommitment<E::Fr>> PackMarlin<E, PC> {
+    /// Universal setup supporting packed circuits up to the given sizes.
+    /// Any trapdoor comes from `rng` and is zeroized afterwards, so pass `OsRng` or another
+    /// `CryptoRng`; `ark_std::test_rng` has a public seed and is only for tests.
+    pub fn setup<R: RngCore + CryptoRng>(
+        num_constraints: usize,
+        num_variables: usize,
+        num_non_zero: usize,
+        rng: &mut R
+    ) -> Result<UniversalParams<E, PC>, PackMarlinError> {
+        setup::universal_setup::<E::Fr, PC, _>(num_constraints, num_variables, num_non_zero, rng)
+    }
+
+    /// SRS sizes needed to index `subcircuit` packed with `params`
+    pub fn index_sizes(subcircuit: &R1CS<E>, params: &PosoParams) -> Result<IndexSizes, PackMarlinError> {
+        let packed = packer::pack(&packer::Subcircuit::from(subcircuit), params, None)?;
+        IndexSizes::of_r1cs::<E>(&packed.r1cs)
+    }
+
+    /// Packs `params.instances` copies of `subcircuit` and indexes the result.
+    /// The Lagrange basis commitments of the SRS are kept in `lagrange_cache` if given,
+    /// and reused from there when they match the SRS
+    pub fn index(
+        srs: &UniversalParams<E, PC>,
+        subcircuit: &R1CS<E>,
+        params: PosoParams,
+        lagrange_cache: Option<&Path>
+    ) -> Result<(ProverKey<E, PC>, VerifierKey<E, PC>), PackMarlinError> {
+        index::index(srs, subcircuit, params, lagrange_cache)
+    }
+
+    /// Proves one full subcircuit witness per instance, in instance order.
+    /// `rng` supplies the zero-knowledge blinding and must be a `CryptoRng`
+    pub fn prove<R: RngCore + CryptoRng>(
+        pk: &ProverKey<E, PC>,
+        witnesses: &[Vec<E::Fr>],
+        rng: &mut R
+    ) -> Result<PackMarlinProof<E, PC>, PackMarlinError> {
+        packer::check_instances(&pk.subcircuit, pk.params.instances, witnesses)?;
+        let packed = packer::pack(&pk.subcircuit, &pk.params, Some(witnesses))?;
+        let circuit = CircomCircuit::<E>{ r1cs: packed.r1cs, witness: packed.witness };
+
+        let cs = ConstraintSystem::<E::Fr>::new_ref();
+        circuit.clone().generate_constraints(cs.clone())?;
+        if !cs.is_satisfied()? {
+            return Err(PackMarlinError::Unsatisfied);
+        }
+
+        prove::prove(pk, circuit, rng)
+    }
+
+    /// Verifies against the public inputs of every instance, in instance order.
+    /// A proof that does not verify is `PackMarlinError::ProofRejected`
+    pub fn verify<R: RngCore>(
+        vk: &VerifierKey<E, PC>,
+        public_inputs: &[Vec<E::Fr>],
+        proof: &PackMarlinProof<E, PC>,
+        rng: &mut R
+    ) -> Result<(), PackMarlinError> {
+        if public_inputs.len() != vk.params.instances {
+            return Err(PackMarlinError::WitnessMismatch(
+                format!("public inputs of {} instances for {}", public_inputs.len(), vk.params.instances)
+            ));
+        }
+        // Marlin would pad a short public input with zeros
+        if let Some(i) = public_inputs.iter().position(|p| p.len() != vk.num_public) {
+            return Err(PackMarlinError::WitnessMismatch(
+                format!("{} public inputs for instance {} instead of {}", public_inputs[i].len(), i, vk.num_public)
+            ));
+        }
+
+        let pubinp = public_inputs.concat();
+        verify::verify(vk, &pubinp, proof, rng)
+    }
+}
+
+impl<E, PC> PackMarlin<E, PC>
+where
+    E: PackCurve,
+    PC: HomomorphicCommitment<E::Fr, UniversalParams = kzg10::UniversalParams<E>>,
+{
+    /// Universal SRS of exactly `sizes`, cut from a larger universal SRS. With the sizes of one
+    /// circuit, e.g. from `VerifierKey::index_sizes`, this is the minimal SRS to index it.
+    /// Keys must then be derived from the trimmed SRS, since shifted commitments depend on its size.
+    pub fn trim_universal(srs: &UniversalParams<E, PC>, sizes: &IndexSizes) -> Result<UniversalParams<E, PC>, PackMarlinError> {
+        setup::trim_universal(srs, sizes.max_degree::<E::Fr>()?)
+    }
+}
@@ -1,209 +1,475 @@
-use ark_circom::{circom::{R1CSFile, R1CS}, CircomCircuit};
-use ark_bls12_381_old::{Bls12_381, Fr as BlsFr, G1Affine};
-use ark_ff::UniformRand;
-use ark_std::{io::{BufReader, Cursor}, cfg_into_iter, start_timer, end_timer};
-use std::{str::FromStr, fs::{read, read_to_string}};
-use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+use ark_bls12_381_old::Bls12_381;
+use ark_bn254_old::Bn254;
+use ark_ff::{BigInteger, PrimeField};
+use ark_poly_commit::PCUniversalParams;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rand::{rngs::StdRng, SeedableRng};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-use rand::rngs::StdRng;
-use ark_marlin::{Marlin, ahp::LabeledPolynomial};
-use ark_poly_commit::{marlin_pc::MarlinKZG10, PolynomialCommitment};
-use ark_poly::{univariate::DensePolynomial, UVPolynomial};
-use blake2::Blake2s;
-use rand_chacha::ChaChaRng;
-use ark_marlin::SimpleHashFiatShamirRng;
-use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
-use ark_marlin::UniversalSRS;
-
-#[macro_use(to_bytes)]
-extern crate ark_ff;
-
-pub mod setup;
-pub mod index;
-pub mod prove;
-pub mod verify;
-
-fn load_values(file: String) -> (R1CS<Bls12_381>, Option<Vec<BlsFr>>, Vec<BlsFr>) {
-    let data = read(file.clone()+"packed_subcircuit.r1cs").unwrap();
-    let witness = read_to_string(file.clone()+"packed_witness.json").unwrap();
-
-    let reader = BufReader::new(Cursor::new(&data[..]));
-    let r1csfile = R1CSFile::<Bls12_381>::new(reader).unwrap();
-    let r1cs = R1CS::from(r1csfile);
-    
-    let witness: Vec<String> = serde_json::from_str(&witness).unwrap();
-
-    // convert witness into field elements
-    let witness = witness
-        .iter()
-        .map(|w| {
-            BlsFr::from_str(&w).unwrap()
-        })
-        .collect::<Vec<BlsFr>>();
+use packmarlin::{artifact_field, r1cs_field, calculate_witnesses, read_json, read_subcircuit, read_witnesses, import_ptau, load_srs, SrsValidation};
+use packmarlin::{contribute, verify_contributions, Contribution, minimal_params, security_bits};
+use packmarlin::{CircuitField, IndexSizes, PackCurve, PackMarlin, PackMarlinError, PackMarlinProof, PosoParams, ProverKey, VerifierKey};
+
+#[derive(Parser)]
+#[command(name = "packmarlin", about = "Prove many instances of a Circom subcircuit with one Marlin proof")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Pairing engine, which must match the prime the circuit was compiled with.
+    /// Detected from `--r1cs` or from the key or proof a command reads, BLS12-381 otherwise
+    #[arg(long, global = true, value_enum)]
+    curve: Option<Curve>,
+    /// Seed every step with the fixed, public test seed. Only for tests: anyone can
+    /// recover the SRS trapdoor and the prover blinding
+    #[arg(long, global = true, hide = true)]
+    test_rng: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a universal SRS sized for the given circuits, or cut one from a larger SRS
+    Setup {
+        /// Subcircuits the SRS must support once packed; the largest decides the size
+        #[arg(long, required_unless_present = "non_zero")]
+        r1cs: Vec<PathBuf>,
+        #[command(flatten)]
+        poso: PosoArgs,
+        /// Relative headroom on every size
+        #[arg(long, default_value_t = 0.05)]
+        margin: f64,
+        /// Explicit sizes, instead of deriving them from `--r1cs`
+        #[arg(long, requires_all = ["variables", "non_zero"], conflicts_with = "r1cs")]
+        constraints: Option<usize>,
+        #[arg(long, requires_all = ["constraints", "non_zero"])]
+        variables: Option<usize>,
+        #[arg(long, requires_all = ["constraints", "variables"])]
+        non_zero: Option<usize>,
+        /// Trim this universal SRS instead of generating a new one
+        #[arg(long)]
+        from: Option<PathBuf>,
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+    },
+    /// Cut the minimal SRS for indexing one circuit out of a universal SRS
+    Trim {
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+        /// Size from the subcircuit packed with the poso params
+        #[arg(long, required_unless_present = "vk", conflicts_with = "vk")]
+        r1cs: Option<PathBuf>,
+        #[command(flatten)]
+        poso: PosoArgs,
+        /// Size exactly from the index info of an existing verifier key
+        #[arg(long)]
+        vk: Option<PathBuf>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Convert a powers-of-tau ceremony file (snarkjs .ptau) into a universal SRS
+    ImportPtau {
+        #[arg(long)]
+        ptau: PathBuf,
+        /// Keep only the powers up to this degree
+        #[arg(long)]
+        max_degree: Option<usize>,
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+    },
+    /// Re-randomize an SRS with fresh secret entropy and record the update
+    Contribute {
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        /// Contributions so far; the new one is appended
+        #[arg(long, default_value = "contributions.bin")]
+        transcript: PathBuf,
+    },
+    /// Check that an SRS follows from an initial one by a chain of recorded contributions
+    VerifyContributions {
+        #[arg(long)]
+        initial: PathBuf,
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+        #[arg(long, default_value = "contributions.bin")]
+        transcript: PathBuf,
+    },
+    /// Pack a subcircuit and index it against an SRS
+    Index {
+        #[arg(long, default_value = "packed_srs.bin")]
+        srs: PathBuf,
+        #[arg(long)]
+        r1cs: PathBuf,
+        #[command(flatten)]
+        poso: PosoArgs,
+        /// Check the SRS points and powers while loading, for an SRS from someone else
+        #[arg(long)]
+        check_srs: bool,
+        /// Keep the Lagrange basis of the SRS here, to skip recomputing it on the next index
+        /// from the same SRS. A file that does not match the SRS is recomputed and overwritten
+        #[arg(long)]
+        lagrange_cache: Option<PathBuf>,
+        #[arg(long, default_value = "packed_pk.bin")]
+        pk: PathBuf,
+        #[arg(long, default_value = "packed_vk.bin")]
+        vk: PathBuf,
+    },
+    /// Prove one witness per instance
+    Prove {
+        #[arg(long, default_value = "packed_pk.bin")]
+        pk: PathBuf,
+        /// Full witnesses in instance order: Circom `.wtns` files, one per instance, or JSON
+        /// arrays with the witness of every instance
+        #[arg(long, num_args = 1.., required_unless_present = "wasm", conflicts_with = "wasm")]
+        witnesses: Vec<PathBuf>,
+        /// Circom-compiled witness generator, to compute the witnesses from `--inputs` instead
+        #[arg(long, requires = "inputs")]
+        wasm: Option<PathBuf>,
+        /// Circom input signals in instance order: JSON objects, or arrays of one per instance
+        #[arg(long, num_args = 1..)]
+        inputs: Vec<PathBuf>,
+        #[arg(long, default_value = "packed_proof.bin")]
+        proof: PathBuf,
+        /// Where to write the public inputs of every instance, as read by `verify`
+        #[arg(long, default_value = "packed_public.json")]
+        public: PathBuf,
+    },
+    /// Verify a proof against the public inputs of every instance
+    Verify {
+        #[arg(long, default_value = "packed_vk.bin")]
+        vk: PathBuf,
+        #[arg(long, default_value = "packed_proof.bin")]
+        proof: PathBuf,
+        #[arg(long, default_value = "packed_public.json")]
+        public: PathBuf,
+    },
+    /// Print a summary of an artifact
+    Inspect {
+        #[arg(value_enum)]
+        kind: Artifact,
+        path: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct PosoArgs {
+    /// Number of packed subcircuit instances
+    #[arg(long, default_value_t = PosoParams::default().instances)]
+    instances: usize,
+    /// Independent random combinations of the instances
+    #[arg(long, conflicts_with = "security_bits")]
+    repetitions: Option<usize>,
+    /// Bits of each poso challenge
+    #[arg(long, default_value_t = PosoParams::default().challenge_bits)]
+    challenge_bits: u32,
+    /// Pick the fewest repetitions reaching this many bits of security
+    #[arg(long)]
+    security_bits: Option<u32>,
+}
 
-    let pubinp: Vec<BlsFr> = witness[1..r1cs.num_inputs].to_vec();
-    let witness = Some(witness);
+impl PosoArgs {
+    fn params(&self) -> Result<PosoParams, PackMarlinError> {
+        match (self.repetitions, self.security_bits) {
+            (_, Some(bits)) => minimal_params(self.instances, self.challenge_bits, bits),
+            (reps, None) => PosoParams::new(
+                self.instances,
+                reps.unwrap_or(PosoParams::default().repetitions),
+                self.challenge_bits
+            ),
+        }
+    }
+}
 
-    // let srs_bytes = std::fs::read("packed_srs.bin").unwrap();
-    // let srs = 
-    //     UniversalSRS::<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>::deserialize_unchecked(&srs_bytes[..]).unwrap();
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Curve {
+    /// Circom's default prime, `--prime bn128`
+    Bn254,
+    /// `--prime bls12381`
+    #[value(name = "bls12-381")]
+    Bls12_381,
+}
+
+impl From<CircuitField> for Curve {
+    fn from(field: CircuitField) -> Self {
+        match field {
+            CircuitField::Bn254 => Curve::Bn254,
+            CircuitField::Bls12_381 => Curve::Bls12_381,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Artifact {
+    Srs,
+    Pk,
+    Vk,
+    Proof,
+}
+
+// The SRS and prover key stay with the prover, so they are stored uncompressed and read unchecked
+// unless asked otherwise. Verifier keys and proofs cross machines and are checked on load.
+fn write_uncompressed<T: CanonicalSerialize>(path: &Path, value: &T) -> Result<(), PackMarlinError> {
+    let mut bytes = vec![];
+    value.serialize_uncompressed(&mut bytes)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
 
-    (r1cs, witness, pubinp)
+fn read_unchecked<T: CanonicalDeserialize>(path: &Path) -> Result<T, PackMarlinError> {
+    let bytes = std::fs::read(path)?;
+    Ok(T::deserialize_unchecked(&bytes[..])?)
 }
 
-#[allow(dead_code)]
-fn setup(file: String, rng: &mut StdRng) {
-    let mut nc: usize = 440000;
-    let mut nv: usize = 561000;
-    let mut nz: usize = 4660000;
-
-    if file == "example" {
-        (nc, nv, nz) = (100, 100, 100);
-    } else if file == "packed" {
-        (nc, nv, nz) = ( 440000,  561000, 4660000);
-    } else if file == "nopack" {
-        (nc, nv, nz) = (1300000, 1300000, 6000000);
-    } 
-    
-    let srs = Marlin::<
-        BlsFr,
-        MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
-        SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-    >::universal_setup(nc, nv, nz, rng)
-    .unwrap();
-
-    // Write srs 
-    let mut srs_bytes = vec![];
-    srs.serialize_uncompressed(&mut srs_bytes).unwrap();
-    std::fs::write(file +"packed_srs.bin", srs_bytes).unwrap();
-}
-
-#[allow(dead_code)]
-fn main1() {
-    let file: String = "./packR1CS/scripts/.output/".to_string();
-
-    let rng = &mut ark_std::test_rng();
-    // setup("packed".to_string(), rng);
-
-    let srs_bytes = std::fs::read("packed_srs.bin").unwrap();
-    let srs = 
-        UniversalSRS::<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>::deserialize_unchecked(&srs_bytes[..]).unwrap();
-
-    let s_index = Instant::now();
-    let (pk, vk, loc) = index::index(&srs.clone());
-    let t_index = s_index.elapsed();
-    println!("index: {:?}", t_index);
-
-    let s_load = Instant::now();
-    let (r1cs, witness, pubinp) 
-        = load_values(file.to_string());
-
-    let mut circuit = CircomCircuit::<Bls12_381>{r1cs, witness};
-    let cs = ConstraintSystem::<BlsFr>::new_ref();
-    circuit.r1cs.wire_mapping = None;
-    circuit.clone().generate_constraints(cs.clone()).unwrap();
-    
-    let is_satisfied = cs.is_satisfied().unwrap();
-    assert!(is_satisfied, "Constraints not satisfied");
-
-    let t_load = s_load.elapsed();
-    println!("load: {:?}", t_load);
-
-    let s_prove = Instant::now();
-    let (ztpf, proof) = prove::prove(&pk.clone(), circuit.clone(), rng, 10000);
-    let t_prove = s_prove.elapsed();
-    println!("prove: {:?}", t_prove);
-
-    let s_verify = Instant::now();
-    let is_valid = verify::verify(&vk, pubinp, proof, ztpf, rng, loc, pk.committer_key.clone());
-    let t_verify = s_verify.elapsed();
-    println!("verify: {:?}", t_verify);
-
-    println!("is_valid: {}", is_valid);
-
-}
-
-#[allow(dead_code)]
-fn main2() {
-    let file: String = "./packR1CS/scripts/.output/".to_string();
-
-    let mut rng = &mut ark_std::test_rng();
-    // setup("packed".to_string(), rng);
-
-    let srs_bytes = std::fs::read("packed_srs.bin").unwrap();
-    let srs = 
-        UniversalSRS::<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>::deserialize_unchecked(&srs_bytes[..]).unwrap();
-
-    let s_load = Instant::now();
-
-        let (r1cs, witness, pubinp) 
-            = load_values(file.to_string());
-
-        let mut circuit = CircomCircuit::<Bls12_381>{r1cs, witness};
-        let cs = ConstraintSystem::<BlsFr>::new_ref();
-        circuit.r1cs.wire_mapping = None;
-        circuit.clone().generate_constraints(cs.clone()).unwrap();
-        
-        let is_satisfied = cs.is_satisfied().unwrap();
-        assert!(is_satisfied, "Constraints not satisfied");
-
-    let t_load = s_load.elapsed();
-    println!("load: {:?}", t_load);
-
-    let s_index = Instant::now();
-        let (pk, vk) = Marlin::<
-            BlsFr,
-            MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
-            SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-        >::index(&srs, circuit.clone())
-        .unwrap();
-    let t_index = s_index.elapsed();
-    println!("index: {:?}", t_index);
-
-    let poso_rand = cfg_into_iter!(0..10000*11)
-        .map(|_| u16::from(u8::rand(&mut rng)) + 1)
-        .collect::<Vec<u16>>();
-    
-    let poso_rand: Vec<BlsFr> = poso_rand
+fn write_compressed<T: CanonicalSerialize>(path: &Path, value: &T) -> Result<(), PackMarlinError> {
+    let mut bytes = vec![];
+    value.serialize(&mut bytes)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn read_checked<T: CanonicalDeserialize>(path: &Path) -> Result<T, PackMarlinError> {
+    let bytes = std::fs::read(path)?;
+    Ok(T::deserialize(&bytes[..])?)
+}
+
+fn write_field_vectors<F: PrimeField>(path: &Path, values: &[Vec<F>]) -> Result<(), PackMarlinError> {
+    let values = values
         .iter()
-        .map(|w| {
-            BlsFr::from(*w)
+        .map(|v| {
+            v.iter()
+                .map(|w| num::BigUint::from_bytes_le(&w.into_repr().to_bytes_le()).to_string())
+                .collect::<Vec<String>>()
         })
-        .collect::<Vec<BlsFr>>();
-    let diff = poso_rand.clone();
-    
-    //commit to diff
-    let diff_time = start_timer!(|| "Committing to diff polynomial");
-    let diff = DensePolynomial::from_coefficients_vec(diff);
-    let diff = LabeledPolynomial::new("diff".to_string(), diff, None, None);
-    let diff_p = vec![&diff].into_iter();
-    let (_, _) = 
-        MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&pk.committer_key.clone(), diff_p, Some(rng)).unwrap();
-    end_timer!(diff_time);
-
-    let s_prove = Instant::now();
-        let proof = Marlin::<
-            BlsFr,
-            MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
-            SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-        >::prove(&pk, circuit.clone(), rng);
-    let t_prove = s_prove.elapsed();
-    println!("prove: {:?}", t_prove);
-
-    let s_verify = Instant::now();
-        let is_valid = Marlin::<
-            BlsFr,
-            MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
-            SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-        >::verify(&vk, &pubinp, &proof.unwrap(), rng);
-    let t_verify = s_verify.elapsed();
-    println!("verify: {:?}", t_verify);
-
-    println!("is_valid: {}", is_valid.unwrap());
+        .collect::<Vec<Vec<String>>>();
+
+    std::fs::write(path, serde_json::to_string(&values)?)?;
+    Ok(())
+}
+
+fn print_params(params: &PosoParams) {
+    println!("poso: {:?}, security: {:.1} bits", params, security_bits(params));
+}
+
+// The curve of the subcircuit, key or proof a command reads; SRS files carry no tag
+fn detect_curve(command: &Command) -> Result<Option<Curve>, PackMarlinError> {
+    let field = match command {
+        Command::Setup { r1cs, .. } => r1cs.first().map(|path| r1cs_field(path)),
+        Command::Trim { r1cs: Some(r1cs), .. } => Some(r1cs_field(r1cs)),
+        Command::Trim { vk: Some(vk), .. } => Some(artifact_field(vk)),
+        Command::Index { r1cs, .. } => Some(r1cs_field(r1cs)),
+        Command::Prove { pk, .. } => Some(artifact_field(pk)),
+        Command::Verify { vk, .. } => Some(artifact_field(vk)),
+        Command::Inspect { kind: Artifact::Pk | Artifact::Vk | Artifact::Proof, path } => Some(artifact_field(path)),
+        _ => None,
+    };
+
+    Ok(field.transpose()?.map(Curve::from))
+}
+
+fn select_curve(curve: Option<Curve>, command: &Command) -> Result<Curve, PackMarlinError> {
+    match (curve, detect_curve(command)?) {
+        (Some(curve), Some(detected)) if curve != detected => Err(PackMarlinError::Parse(
+            "--curve does not match the curve of the subcircuit, key or proof".into()
+        )),
+        (curve, detected) => Ok(curve.or(detected).unwrap_or(Curve::Bls12_381)),
+    }
 }
 
 fn main() {
-    main1();
-    // main2();
-}
\ No newline at end of file
+    let cli = Cli::parse();
+    let rng = &mut if cli.test_rng {
+        ark_std::test_rng()
+    } else {
+        StdRng::from_entropy()
+    };
+
+    let result = select_curve(cli.curve, &cli.command).and_then(|curve| match curve {
+        Curve::Bn254 => run::<Bn254>(cli.command, rng),
+        Curve::Bls12_381 => run::<Bls12_381>(cli.command, rng),
+    });
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run<E: PackCurve>(command: Command, rng: &mut StdRng) -> Result<(), PackMarlinError> {
+    match command {
+        Command::Setup { r1cs, poso, margin, constraints, variables, non_zero, from, srs } => {
+            let sizes = match (constraints, variables, non_zero) {
+                (Some(num_constraints), Some(num_variables), Some(num_non_zero)) =>
+                    IndexSizes { num_constraints, num_variables, num_non_zero },
+                _ => {
+                    let params = poso.params()?;
+                    print_params(&params);
+
+                    r1cs.iter()
+                        .map(|path| PackMarlin::<E>::index_sizes(&read_subcircuit(path)?, &params))
+                        .collect::<Result<Vec<IndexSizes>, PackMarlinError>>()?
+                        .into_iter()
+                        .reduce(IndexSizes::max)
+                        .expect("clap requires --r1cs without explicit sizes")
+                }
+            };
+            let sizes = sizes.with_margin(margin);
+            let max_degree = sizes.max_degree::<E::Fr>()?;
+            println!("{:?}, max degree: {}", sizes, max_degree);
+
+            let s_setup = Instant::now();
+            let universal = match from {
+                Some(from) => load_srs::<E, _>(&from, Some(max_degree), SrsValidation::Unchecked, rng)?,
+                None => PackMarlin::<E>::setup(sizes.num_constraints, sizes.num_variables, sizes.num_non_zero, rng)?,
+            };
+            println!("setup: {:?}", s_setup.elapsed());
+
+            write_uncompressed(&srs, &universal)?;
+        }
+
+        Command::Trim { srs, r1cs, poso, vk, out } => {
+            let sizes = match (r1cs, vk) {
+                (_, Some(vk)) => read_checked::<VerifierKey<E>>(&vk)?.index_sizes(),
+                (Some(r1cs), None) => PackMarlin::<E>::index_sizes(&read_subcircuit(&r1cs)?, &poso.params()?)?,
+                (None, None) => unreachable!(),
+            };
+            let max_degree = sizes.max_degree::<E::Fr>()?;
+            println!("{:?}, max degree: {}", sizes, max_degree);
+
+            // only the powers the circuit needs are read
+            let universal = load_srs::<E, _>(&srs, Some(max_degree), SrsValidation::Unchecked, rng)?;
+            write_uncompressed(&out, &universal)?;
+        }
+
+        Command::ImportPtau { ptau, max_degree, srs } => {
+            let s_import = Instant::now();
+            let universal = import_ptau::<E, _>(&ptau, max_degree, rng)?;
+            println!("import: {:?}, max degree: {}", s_import.elapsed(), universal.max_degree());
+
+            write_uncompressed(&srs, &universal)?;
+        }
+
+        Command::Contribute { srs, out, transcript } => {
+            let universal = load_srs::<E, _>(&srs, None, SrsValidation::Subgroup, rng)?;
+            let mut contributions: Vec<Contribution<E>> = if transcript.exists() {
+                read_checked(&transcript)?
+            } else {
+                vec![]
+            };
+
+            let s_contribute = Instant::now();
+            let (updated, contribution) = contribute(&universal, rng)?;
+            println!("contribute: {:?}", s_contribute.elapsed());
+
+            contributions.push(contribution);
+            write_uncompressed(&out, &updated)?;
+            write_compressed(&transcript, &contributions)?;
+        }
+
+        Command::VerifyContributions { initial, srs, transcript } => {
+            // the powers are checked by `verify_contributions`
+            let initial = load_srs::<E, _>(&initial, None, SrsValidation::Subgroup, rng)?;
+            let universal = load_srs::<E, _>(&srs, None, SrsValidation::Subgroup, rng)?;
+            let contributions: Vec<Contribution<E>> = read_checked(&transcript)?;
+
+            let is_valid = verify_contributions(&initial, &universal, &contributions, rng)?;
+            println!("contributions: {}, is_valid: {}", contributions.len(), is_valid);
+            if !is_valid {
+                std::process::exit(1);
+            }
+        }
+
+        Command::Index { srs, r1cs, poso, check_srs, lagrange_cache, pk, vk } => {
+            let params = poso.params()?;
+            print_params(&params);
+
+            let sub = read_subcircuit(&r1cs)?;
+            let validation = if check_srs { SrsValidation::Full } else { SrsValidation::Unchecked };
+            let max_degree = PackMarlin::<E>::index_sizes(&sub, &params)?.max_degree::<E::Fr>()?;
+            let universal = load_srs::<E, _>(&srs, Some(max_degree), validation, rng)?;
+
+            let s_index = Instant::now();
+            let (prover_key, verifier_key) = PackMarlin::<E>::index(&universal, &sub, params, lagrange_cache.as_deref())?;
+            println!("index: {:?}", s_index.elapsed());
+
+            write_uncompressed(&pk, &prover_key)?;
+            write_compressed(&vk, &verifier_key)?;
+        }
+
+        Command::Prove { pk, witnesses, wasm, inputs, proof, public } => {
+            let prover_key: ProverKey<E> = read_unchecked(&pk)?;
+            let num_variables = prover_key.num_variables();
+            let witnesses = match wasm {
+                Some(wasm) => {
+                    let s_witness = Instant::now();
+                    let witnesses = inputs
+                        .iter()
+                        .map(|path| calculate_witnesses::<E::Fr>(&wasm, path, num_variables))
+                        .collect::<Result<Vec<_>, PackMarlinError>>()?
+                        .concat();
+                    println!("witness: {:?}", s_witness.elapsed());
+
+                    witnesses
+                }
+                None => witnesses
+                    .iter()
+                    .map(|path| read_witnesses::<E::Fr>(path, num_variables))
+                    .collect::<Result<Vec<_>, PackMarlinError>>()?
+                    .concat(),
+            };
+
+            let s_prove = Instant::now();
+            let pack_proof = PackMarlin::<E>::prove(&prover_key, &witnesses, rng)?;
+            println!("prove: {:?}", s_prove.elapsed());
+
+            let pubinp = witnesses
+                .iter()
+                .map(|w| prover_key.public_inputs(w))
+                .collect::<Result<Vec<Vec<E::Fr>>, PackMarlinError>>()?;
+
+            write_compressed(&proof, &pack_proof)?;
+            write_field_vectors(&public, &pubinp)?;
+        }
+
+        Command::Verify { vk, proof, public } => {
+            let verifier_key: VerifierKey<E> = read_checked(&vk)?;
+            let pack_proof: PackMarlinProof<E> = read_checked(&proof)?;
+            let pubinp = read_json(&public)?;
+
+            let s_verify = Instant::now();
+            let result = PackMarlin::<E>::verify(&verifier_key, &pubinp, &pack_proof, rng);
+            println!("verify: {:?}", s_verify.elapsed());
+
+            println!("is_valid: {}", result.is_ok());
+            result?;
+        }
+
+        Command::Inspect { kind, path } => match kind {
+            Artifact::Srs => {
+                let universal = load_srs::<E, _>(&path, None, SrsValidation::Unchecked, rng)?;
+                println!("max degree: {}", universal.max_degree());
+            }
+            Artifact::Pk => {
+                let prover_key: ProverKey<E> = read_unchecked(&path)?;
+                print_params(&prover_key.params());
+            }
+            Artifact::Vk => {
+                let verifier_key: VerifierKey<E> = read_checked(&path)?;
+                println!("version: {}, size: {} bytes", verifier_key.version(), verifier_key.serialized_size());
+                print_params(&verifier_key.params());
+                println!(
+                    "constraints: {}, variables: {}, non-zero: {}",
+                    verifier_key.num_constraints(),
+                    verifier_key.num_variables(),
+                    verifier_key.num_non_zero()
+                );
+            }
+            Artifact::Proof => {
+                let pack_proof: PackMarlinProof<E> = read_checked(&path)?;
+                println!("version: {}, size: {} bytes", pack_proof.version(), pack_proof.serialized_size());
+            }
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use ark_circom::circom::Constraints;
+use ark_ec_old::PairingEngine;
+use ark_ff::{One, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::{ start_timer, end_timer };
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::layout::{Matrix, PosoLayout, RandCoeff};
+use crate::params::PosoParams;
+use crate::{ R1CSFile, R1CS };
+use crate::{ BufReader, Cursor, read };
+use crate::error::PackMarlinError;
+
+/// Packed circuit, its witness (if instance witnesses were given) and where randomness enters it
+#[derive(Clone)]
+pub(crate) struct Packed<E: PairingEngine> {
+    pub r1cs: R1CS<E>,
+    pub witness: Option<Vec<E::Fr>>,
+    pub layout: PosoLayout,
+}
+
+/// The parts of a subcircuit the packer needs, kept in the prover key to pack instance witnesses
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct Subcircuit<E: PairingEngine> {
+    pub num_inputs: usize,
+    pub num_aux: usize,
+    pub num_variables: usize,
+    pub constraints: Vec<Constraints<E>>,
+}
+
+impl<E: PairingEngine> From<&R1CS<E>> for Subcircuit<E> {
+    fn from(r1cs: &R1CS<E>) -> Self {
+        Subcircuit {
+            num_inputs: r1cs.num_inputs,
+            num_aux: r1cs.num_aux,
+            num_variables: r1cs.num_variables,
+            constraints: r1cs.constraints.clone(),
+        }
+    }
+}
+
+/// Reads a Circom subcircuit, dropping its wire mapping since packing renumbers the wires.
+/// `E` must match the prime it was compiled for, see `curve::r1cs_field`
+pub fn read_subcircuit<E: PairingEngine>(path: &Path) -> Result<R1CS<E>, PackMarlinError> {
+    let data = read(path)?;
+
+    let reader = BufReader::new(Cursor::new(&data[..]));
+    let r1csfile = R1CSFile::<E>::new(reader)
+        .map_err(|e| PackMarlinError::Parse(format!("{}: {}", path.display(), e)))?;
+    let mut r1cs = R1CS::from(r1csfile);
+    r1cs.wire_mapping = None;
+
+    Ok(r1cs)
+}
+
+fn check_shape<E: PairingEngine>(sub: &Subcircuit<E>, instances: usize, witnesses: &[Vec<E::Fr>]) -> Result<(), PackMarlinError> {
+    if witnesses.len() != instances {
+        return Err(PackMarlinError::WitnessMismatch(
+            format!("{} witnesses for {} instances", witnesses.len(), instances)
+        ));
+    }
+    if let Some(i) = witnesses.iter().position(|w| w.len() != sub.num_variables || !w[0].is_one()) {
+        return Err(PackMarlinError::WitnessMismatch(
+            format!("witness {} does not have the {} wires of the subcircuit, starting with one", i, sub.num_variables)
+        ));
+    }
+
+    Ok(())
+}
+
+fn eval<E: PairingEngine>(lc: &[(usize, E::Fr)], w: &[E::Fr]) -> E::Fr {
+    lc.iter().fold(E::Fr::zero(), |acc, (wire, coeff)| acc + w[*wire] * coeff)
+}
+
+/// Checks every instance witness against the constraints of `sub`. Packing alone does not catch
+/// an unsatisfied instance: the products are computed from the witnesses, and without randomness
+/// the combinations only need the errors of each constraint to cancel across instances
+pub(crate) fn check_instances<E: PairingEngine>(
+    sub: &Subcircuit<E>,
+    instances: usize,
+    witnesses: &[Vec<E::Fr>]
+) -> Result<(), PackMarlinError> {
+    check_shape(sub, instances, witnesses)?;
+
+    let satisfied = witnesses.iter().all(|w| {
+        sub.constraints
+            .iter()
+            .all(|(a, b, c)| eval::<E>(a, w) * eval::<E>(b, w) == eval::<E>(c, w))
+    });
+    if !satisfied {
+        return Err(PackMarlinError::Unsatisfied);
+    }
+
+    Ok(())
+}
+
+// Packed wires are laid out as
+//   [one | public signals of every instance | private signals of every instance | products]
+// so the instance part of the witness keeps Circom's public-first ordering.
+struct WireMap {
+    sub_public: usize,
+    sub_aux: usize,
+    sub_inputs: usize,
+    packed_inputs: usize,
+}
+
+impl WireMap {
+    fn new<E: PairingEngine>(sub: &Subcircuit<E>, instances: usize) -> Self {
+        let sub_public = sub.num_inputs - 1;
+        WireMap {
+            sub_public,
+            sub_aux: sub.num_aux,
+            sub_inputs: sub.num_inputs,
+            packed_inputs: 1 + instances * sub_public,
+        }
+    }
+
+    fn map(&self, instance: usize, wire: usize) -> usize {
+        if wire == 0 {
+            0
+        } else if wire < self.sub_inputs {
+            1 + instance * self.sub_public + (wire - 1)
+        } else {
+            self.packed_inputs + instance * self.sub_aux + (wire - self.sub_inputs)
+        }
+    }
+}
+
+/// Packs `params.instances` copies of `sub` side by side. Constraint k of instance i,
+/// `a_k(w_i) * b_k(w_i) = c_k(w_i)`, is split into a product constraint `a_k(w_i) * b_k(w_i) = p_ik + c0_k`
+/// on a new wire p_ik, with c0_k the constant term of c_k, and the linear error `e_ik = p_ik - c'_k(w_i)`
+/// of the remaining terms. The errors are checked by the poso combinations: for every repetition j and
+/// constraint k, one row `0 = sum_i r_ij * e_ik` whose C coefficients of instance i the randomness of
+/// slot `i * repetitions + j` multiplies. Unrandomized, every r_ij is 1.
+pub(crate) fn pack<E: PairingEngine>(
+    sub: &Subcircuit<E>,
+    params: &PosoParams,
+    witnesses: Option<&[Vec<E::Fr>]>
+) -> Result<Packed<E>, PackMarlinError> {
+    let pack_time = start_timer!(|| "Packing subcircuit instances");
+    let instances = params.instances;
+    let repetitions = params.repetitions;
+    let num_sub = sub.constraints.len();
+    let wires = WireMap::new(sub, instances);
+
+    let prod_base = wires.packed_inputs + instances * sub.num_aux;
+    let num_variables = prod_base + instances * num_sub;
+    let product = |instance: usize, k: usize| prod_base + instance * num_sub + k;
+
+    let remap = |instance: usize, lc: &[(usize, E::Fr)]| {
+        lc.iter()
+            .map(|(w, coeff)| (wires.map(instance, *w), *coeff))
+            .collect::<Vec<_>>()
+    };
+
+    // constant term and linear terms of every c, with repeated wires merged and zeros dropped,
+    // since the index keeps one entry per non-zero
+    let linear = sub.constraints
+        .iter()
+        .map(|(_, _, c)| {
+            let mut terms = BTreeMap::new();
+            for (w, coeff) in c {
+                *terms.entry(*w).or_insert_with(E::Fr::zero) += coeff;
+            }
+            let constant = terms.remove(&0).unwrap_or_else(E::Fr::zero);
+            terms.retain(|_, coeff| !coeff.is_zero());
+            (constant, terms)
+        })
+        .collect::<Vec<(E::Fr, BTreeMap<usize, E::Fr>)>>();
+
+    let mut constraints: Vec<Constraints<E>> = Vec::with_capacity((instances + repetitions) * num_sub);
+    for i in 0..instances {
+        for (k, ((a, b, _), (constant, _))) in sub.constraints.iter().zip(&linear).enumerate() {
+            let mut c = vec![(product(i, k), E::Fr::one())];
+            if !constant.is_zero() {
+                c.push((0, *constant));
+            }
+            constraints.push((remap(i, a), remap(i, b), c));
+        }
+    }
+
+    let mut layout = PosoLayout { num_slots: params.num_challenges(), coefficients: vec![] };
+    for j in 0..repetitions {
+        for (k, (_, terms)) in linear.iter().enumerate() {
+            let row = constraints.len();
+            let mut c = Vec::with_capacity(instances * (terms.len() + 1));
+
+            for i in 0..instances {
+                let slot = i * repetitions + j;
+                let error = std::iter::once((product(i, k), E::Fr::one()))
+                    .chain(terms.iter().map(|(w, coeff)| (wires.map(i, *w), -*coeff)));
+
+                for (col, coeff) in error {
+                    layout.coefficients.push(RandCoeff { slot, matrix: Matrix::C, row, col });
+                    c.push((col, coeff));
+                }
+            }
+
+            constraints.push((vec![], vec![], c));
+        }
+    }
+
+    let r1cs = R1CS {
+        num_inputs: wires.packed_inputs,
+        num_aux: num_variables - wires.packed_inputs,
+        num_variables,
+        constraints,
+        wire_mapping: None,
+    };
+
+    let witness = witnesses.map(|witnesses| {
+        check_shape(sub, instances, witnesses)?;
+
+        let mut packed = vec![E::Fr::zero(); num_variables];
+        packed[0] = E::Fr::one();
+        for (i, w) in witnesses.iter().enumerate() {
+            for (wire, value) in w.iter().enumerate().skip(1) {
+                packed[wires.map(i, wire)] = *value;
+            }
+            for (k, ((a, b, _), (constant, _))) in sub.constraints.iter().zip(&linear).enumerate() {
+                packed[product(i, k)] = eval::<E>(a, w) * eval::<E>(b, w) - constant;
+            }
+        }
+
+        Ok(packed)
+    })
+    .transpose()?;
+
+    end_timer!(pack_time);
+
+    Ok(Packed { r1cs, witness, layout })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::{Bls12_381, Fr};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use crate::CircomCircuit;
+    use crate::test_utils::{square, square_witness};
+
+    fn is_satisfied(packed: &Packed<Bls12_381>) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let circuit = CircomCircuit { r1cs: packed.r1cs.clone(), witness: packed.witness.clone() };
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    // What the prover does to the index: every coefficient of a slot times its randomness
+    fn randomize(packed: &mut Packed<Bls12_381>, poso_rand: &[u64]) {
+        for coeff in &packed.layout.coefficients {
+            let (_, _, c) = &mut packed.r1cs.constraints[coeff.row];
+            for (_, value) in c.iter_mut().filter(|(w, _)| *w == coeff.col) {
+                *value *= Fr::from(poso_rand[coeff.slot]);
+            }
+        }
+    }
+
+    fn pack_square(witnesses: &[Vec<Fr>]) -> Packed<Bls12_381> {
+        let params = PosoParams::new(witnesses.len(), 2, 8).unwrap();
+        pack(&Subcircuit::from(&square::<Bls12_381>()), &params, Some(witnesses)).unwrap()
+    }
+
+    #[test]
+    fn packs_one_slot_per_instance_and_repetition() {
+        let packed = pack_square(&[square_witness::<Bls12_381>(3, 9), square_witness::<Bls12_381>(5, 25)]);
+
+        assert_eq!(packed.layout.num_slots, 4);
+        // 2 instances * 2 product constraints, then 2 repetitions * 2 combinations
+        assert_eq!(packed.r1cs.constraints.len(), 8);
+        assert!(packed.layout.coefficients.iter().all(|c| c.matrix == Matrix::C && c.row >= 4));
+        assert!(is_satisfied(&packed));
+    }
+
+    #[test]
+    fn honest_witnesses_stay_satisfied_when_randomized() {
+        let mut packed = pack_square(&[square_witness::<Bls12_381>(3, 9), square_witness::<Bls12_381>(5, 25)]);
+
+        randomize(&mut packed, &[3, 200, 17, 42]);
+        assert!(is_satisfied(&packed));
+    }
+
+    #[test]
+    fn randomness_catches_errors_that_cancel_across_instances() {
+        // y is off by -1 in one instance and by +1 in the other
+        let mut packed = pack_square(&[square_witness::<Bls12_381>(3, 10), square_witness::<Bls12_381>(3, 8)]);
+        assert!(is_satisfied(&packed));
+
+        randomize(&mut packed, &[3, 200, 17, 42]);
+        assert!(!is_satisfied(&packed));
+    }
+
+    #[test]
+    fn checks_every_instance_on_its_own() {
+        let params = PosoParams::new(2, 2, 8).unwrap();
+        let sub = Subcircuit::from(&square::<Bls12_381>());
+
+        let honest = [square_witness::<Bls12_381>(3, 9), square_witness::<Bls12_381>(5, 25)];
+        assert!(check_instances(&sub, params.instances, &honest).is_ok());
+
+        let cancelling = [square_witness::<Bls12_381>(3, 10), square_witness::<Bls12_381>(3, 8)];
+        assert!(matches!(check_instances(&sub, params.instances, &cancelling), Err(PackMarlinError::Unsatisfied)));
+    }
+
+    #[test]
+    fn rejects_witnesses_of_another_shape() {
+        let params = PosoParams::new(2, 1, 8).unwrap();
+        let sub = Subcircuit::from(&square::<Bls12_381>());
+
+        let one_instance = [square_witness::<Bls12_381>(3, 9)];
+        assert!(matches!(pack(&sub, &params, Some(&one_instance[..])), Err(PackMarlinError::WitnessMismatch(_))));
+
+        let short = [square_witness::<Bls12_381>(3, 9), vec![Fr::one(), Fr::from(9u64)]];
+        assert!(matches!(pack(&sub, &params, Some(&short[..])), Err(PackMarlinError::WitnessMismatch(_))));
+    }
+}
@@ -0,0 +1,43 @@
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::rand::RngCore;
+
+use crate::error::PackMarlinError;
+
+/// Shape of the positive-only random combination (poso) check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PosoParams {
+    /// Number of packed subcircuit instances
+    pub instances: usize,
+    /// Independent random combinations of the instances
+    pub repetitions: usize,
+    /// Challenges are drawn uniformly from [1, 2^challenge_bits]
+    pub challenge_bits: u32,
+}
+
+impl Default for PosoParams {
+    fn default() -> Self {
+        PosoParams { instances: 10000, repetitions: 11, challenge_bits: 8 }
+    }
+}
+
+impl PosoParams {
+    pub fn new(instances: usize, repetitions: usize, challenge_bits: u32) -> Result<Self, PackMarlinError> {
+        if instances == 0 || repetitions == 0 {
+            return Err(PackMarlinError::InvalidParams("empty poso check"));
+        }
+        if !(1..64).contains(&challenge_bits) {
+            return Err(PackMarlinError::InvalidParams("challenge_bits must be in 1..64"));
+        }
+
+        Ok(PosoParams { instances, repetitions, challenge_bits })
+    }
+
+    /// Number of randomness slots, one challenge each
+    pub fn num_challenges(&self) -> usize {
+        self.instances * self.repetitions
+    }
+
+    pub(crate) fn sample_challenge<R: RngCore>(&self, rng: &mut R) -> u64 {
+        (rng.next_u64() & ((1u64 << self.challenge_bits) - 1)) + 1
+    }
+}
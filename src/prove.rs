@@ -1,228 +1,261 @@
-use std::collections::BTreeMap;
-use ark_marlin::ahp::LabeledPolynomial;
+use ark_marlin::ahp::{AHPForR1CS, LabeledPolynomial};
 use ark_marlin::rng::FiatShamirRng;
 use ark_poly::{
     univariate::DensePolynomial, EvaluationDomain, Evaluations as EvaluationsOnDomain,
     GeneralEvaluationDomain, UVPolynomial,
 };
-use ark_poly_commit::marlin_pc::{Commitment, CommitterKey};
-use ark_poly_commit::{PolynomialCommitment, LabeledCommitment};
+use ark_poly_commit::{PolynomialCommitment, LabeledCommitment, QuerySet};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 use crate::{ CircomCircuit };
 use ark_std::{ start_timer, end_timer , cfg_into_iter, UniformRand};
-use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
 
 
 use crate::Blake2s;
 use crate::SimpleHashFiatShamirRng;
 use crate::ChaChaRng;
-use crate::MarlinKZG10;
 use crate::Marlin;
-use crate::{ Bls12_381, BlsFr };
-use ark_marlin::{IndexProverKey, Proof};
-use ark_ff::Zero;
+use crate::index::{val_index, ProverKey};
+use crate::layout::{diff_support, IndexedCoeff, Matrix};
+use crate::params::PosoParams;
+use crate::curve::PackCurve;
+use crate::format::{CurveTag, Version, PROOF_VERSION};
+use crate::setup::eval_domain;
+use crate::commitment::{HomomorphicCommitment, MarlinKZG};
+use crate::error::PackMarlinError;
+use ark_marlin::{IndexVerifierKey, Proof};
+use ark_ff::{One, PrimeField, Zero};
 
 pub const PROTOCOL_NAME: &'static [u8] = b"packmarlin";
 
-pub(crate) fn write_poso_rand(poso_rand: Vec<u16>) {
-    // convert poso_rand to vector of strings
-    let poso_rand: Vec<String> = poso_rand
-        .iter()
-        .map(|w| {
-            w.to_string()
-        })
-        .collect::<Vec<String>>();
-
-    let poso_rand = serde_json::to_string(&poso_rand).unwrap();
-    std::fs::write("./packR1CS/scripts/.output/poso_rand.json", poso_rand).unwrap();
+// poso_rand is drawn after the prover commits to the witness in the first Marlin round,
+// whose commitments the Marlin verifier then checks against the rest of the proof
+pub(crate) fn poso_challenges<F: PrimeField, PC: HomomorphicCommitment<F>>(
+    vk: &IndexVerifierKey<F,PC>,
+    public_input: &[F],
+    first_comms: &[PC::Commitment],
+    params: &PosoParams
+) -> Result<Vec<u64>, PackMarlinError> {
+    let mut fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(&to_bytes![&PROTOCOL_NAME, vk, &public_input.to_vec()]?);
+    fs_rng.absorb(&first_comms.to_vec());
+
+    Ok(cfg_into_iter!(0..params.num_challenges())
+        .map(|_| params.sample_challenge(&mut fs_rng))
+        .collect::<Vec<u64>>())
 }
 
-pub(crate) struct ZtProof {
-    pub quotient_poly_comm: LabeledCommitment<Commitment<Bls12_381>>,
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub(crate) struct ZtProof<F: PrimeField, PC: HomomorphicCommitment<F>> {
+    pub quotient_poly_comm: PC::Commitment,
+    pub diff_eval: F,
+    pub quotient_eval: F,
+    pub opening_proof: PC::BatchProof,
 }
 
-fn zt_prover(ck: CommitterKey<Bls12_381>, wit_diff_poly: DensePolynomial<BlsFr>) -> ZtProof {
-    let domain = GeneralEvaluationDomain::new(80000).unwrap();
+/// Proof for a packed circuit: the Marlin proof against the randomized index, whose first-round
+/// commitments to the witness fix poso_rand, and the zero test on the diff polynomial.
+/// Serializes with a leading format version and curve tag so stale or foreign proofs fail to load.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PackMarlinProof<E: PackCurve, PC: HomomorphicCommitment<E::Fr> = MarlinKZG<E>> {
+    pub(crate) version: Version<PROOF_VERSION>,
+    pub(crate) curve: CurveTag<E>,
+    pub(crate) diff_comm: PC::Commitment,
+    pub(crate) proof: Proof<E::Fr, PC>,
+    pub(crate) zt_proof: ZtProof<E::Fr, PC>,
+}
 
-    let (_, r) =  wit_diff_poly.divide_by_vanishing_poly(domain).unwrap();
+impl<E: PackCurve, PC: HomomorphicCommitment<E::Fr>> PackMarlinProof<E, PC> {
+    pub fn version(&self) -> u32 {
+        PROOF_VERSION
+    }
+}
 
-    let quotient_poly = LabeledPolynomial::new("quotient_poly".to_string(), r, None, None);
+// Evaluation point and opening challenge, bound to both commitments
+pub(crate) fn zt_challenges<F: PrimeField, PC: HomomorphicCommitment<F>>(
+    diff_comm: &LabeledCommitment<PC::Commitment>,
+    quotient_poly_comm: &LabeledCommitment<PC::Commitment>,
+) -> Result<(F, F), PackMarlinError> {
+    let mut fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(&to_bytes![&PROTOCOL_NAME]?);
+    fs_rng.absorb(diff_comm);
+    fs_rng.absorb(quotient_poly_comm);
 
-    let (quotient_poly_comm, _) = 
-        MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&ck.clone(), vec![&quotient_poly].into_iter(), None).unwrap();
+    let z = F::rand(&mut fs_rng);
+    let opening_challenge = F::rand(&mut fs_rng);
 
-    ZtProof {
-        quotient_poly_comm: quotient_poly_comm[0].clone(),
-    }
+    Ok((z, opening_challenge))
 }
 
-pub(crate) fn prove(
-    pk: &IndexProverKey<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>,
-    circuit: CircomCircuit<Bls12_381>,
-    rng: &mut StdRng,
-    poso_size: usize
-) -> (ZtProof, Proof<BlsFr, MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>) {
-
-    let domain_h = GeneralEvaluationDomain::new(pk.clone().index.index_info.num_constraints).unwrap();
-    let domain_x = GeneralEvaluationDomain::new(circuit.r1cs.num_inputs).unwrap();
-    let domain_k = GeneralEvaluationDomain::new(pk.clone().index.index_info.num_non_zero).unwrap();
-
-    let witness = circuit.witness.clone().unwrap();
-    let public_input: Vec<BlsFr> = witness[1..circuit.r1cs.num_inputs].to_vec();
-    let pub_poly = domain_h.fft(&public_input);
-    let mut w_extended = witness.clone();
-    w_extended.extend(vec![
-        BlsFr::zero();
-        domain_h.size() - domain_x.size()
-    ]);
-
-    let w_poly_time = start_timer!(|| "Computing w polynomial");
-    
-    let ratio = domain_h.size() / domain_x.size();
-    let v_h = domain_h.vanishing_polynomial().into();
-    let w_poly_evals = cfg_into_iter!(0..domain_h.size())
-        .map(|k| {
-            if k % ratio == 0 {
-                BlsFr::zero()
-            } else {
-                w_extended[k - (k / ratio) - 1] - &pub_poly[k]
-            }
-        })
-        .collect();
-
-    let w_poly = &EvaluationsOnDomain::from_vec_and_domain(w_poly_evals, domain_h)
-        .interpolate()
-        + &(&DensePolynomial::from_coefficients_slice(&[BlsFr::rand(rng)]) * &v_h);
-    let (w_poly, remainder) = w_poly.divide_by_vanishing_poly(domain_x).unwrap();
-    assert!(remainder.is_zero());
-
-    let w = LabeledPolynomial::new("w".to_string(), w_poly, None, Some(1));
-    end_timer!(w_poly_time);
-
-
-    let w_poly_comm_time = start_timer!(|| "Committing to w polynomial");
-
-    let binding = w.clone();
-    let w_p = vec![&binding].into_iter();
-    let (witness_comm, _) = 
-        MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&pk.clone().committer_key, w_p, Some(rng)).unwrap();
-
-    end_timer!(w_poly_comm_time);
-
+pub(crate) fn zt_query_set<F: PrimeField>(z: F) -> QuerySet<F> {
+    let mut query_set = QuerySet::new();
+    query_set.insert(("diff".to_string(), ("z".to_string(), z)));
+    query_set.insert(("quotient_poly".to_string(), ("z".to_string(), z)));
 
-    // compute poso_rand
-    let poso_time = start_timer!(|| "Computing poso_rand");
-    let mut fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(&to_bytes![&PROTOCOL_NAME, &pk.index_vk.clone(), &public_input].unwrap());
-    fs_rng.absorb(&witness_comm);
+    query_set
+}
 
-    let poso_rand = cfg_into_iter!(0..poso_size*11)
-        .map(|_| u16::from(u8::rand(&mut fs_rng)) + 1)
-        .collect::<Vec<u16>>();
+// The points of `domain` at the positions [lo, hi)
+pub(crate) fn support_points<F: PrimeField>(
+    domain: GeneralEvaluationDomain<F>,
+    (lo, hi): (usize, usize)
+) -> impl Iterator<Item = F> {
+    let generator = domain.element(1);
+    (lo..hi).scan(domain.element(lo), move |point, _| {
+        let current = *point;
+        *point *= generator;
+        Some(current)
+    })
+}
 
-    write_poso_rand(poso_rand.clone());
-    end_timer!(poso_time);
-    
-    // re run packer and re-read witness
-
-    // Update witness with poso_rand starting at offset. 
-    // The default values are 1, so add poso_rand[i] - 1
-
-    let witness_time = start_timer!(|| "Updating witness with poso_rand");
-    let mut new_witness = circuit.witness.clone().unwrap();
-    let offset = 0;
-    for i in 0..11 {
-        for j in 0..poso_size {
-            new_witness[offset + i*11 + j] = new_witness[offset + i*11 + j] + BlsFr::from(poso_rand[i*11 + j] - 1);
-        }
+// Z_S for the support S = [lo, hi) of `domain`, multiplied up in a product tree
+fn support_vanishing_poly<F: PrimeField>(domain: GeneralEvaluationDomain<F>, support: (usize, usize)) -> DensePolynomial<F> {
+    let mut layer = support_points(domain, support)
+        .map(|point| DensePolynomial::from_coefficients_vec(vec![-point, F::one()]))
+        .collect::<Vec<_>>();
+    if layer.is_empty() {
+        return DensePolynomial::from_coefficients_vec(vec![F::one()]);
     }
-    end_timer!(witness_time);
 
-    // let packer_time = start_timer!(|| "Running packer.js with randomness");
-    // let _ = Command::new("node")
-    //     .arg("./packR1CS/scripts/packer.js")
-    //     .arg("rand")
-    //     .output()
-    //     .expect("packer.js failed");
-    // end_timer!(packer_time);
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => a * b,
+                _ => pair[0].clone(),
+            })
+            .collect();
+    }
 
-    // let file_time = start_timer!(|| "Loading new witness file");
-    // let file: String = "./packR1CS/scripts/.output/".to_string();
+    layer.pop().unwrap()
+}
 
-    // let data = read(file.clone()+"packed_subcircuit.r1cs").unwrap();
-    // let witness = read_to_string(file.clone()+"packed_witness.json").unwrap();
+// diff vanishes on K outside of the support S of the randomized positions, so diff * Z_S = q * Z_K.
+// Proves it by committing to q and batch opening diff and q at a Fiat-Shamir point
+fn zt_prover<F: PrimeField, PC: HomomorphicCommitment<F>, R: RngCore + CryptoRng>(
+    ck: PC::CommitterKey,
+    domain: GeneralEvaluationDomain<F>,
+    support: (usize, usize),
+    diff: &LabeledPolynomial<F>,
+    diff_comm: &LabeledCommitment<PC::Commitment>,
+    diff_rand: &PC::Randomness,
+    rng: &mut R,
+) -> Result<ZtProof<F, PC>, PackMarlinError> {
+    let zt_time = start_timer!(|| "Proving zt");
+
+    let (q, remainder) = (diff.polynomial() * &support_vanishing_poly(domain, support))
+        .divide_by_vanishing_poly(domain)
+        .ok_or_else(|| PackMarlinError::Marlin("cannot divide diff by the vanishing polynomial".into()))?;
+    if !remainder.is_zero() {
+        return Err(PackMarlinError::Marlin("diff does not vanish on K outside of the randomized positions".into()));
+    }
 
-    // let reader = BufReader::new(Cursor::new(&data[..]));
-    // let r1csfile = R1CSFile::<Bls12_381>::new(reader).unwrap();
-    // let r1cs = R1CS::from(r1csfile);
+    let quotient_poly = LabeledPolynomial::new("quotient_poly".to_string(), q, None, Some(1));
+
+    let (quotient_poly_comm, quotient_rand) = 
+        PC::commit(&ck, vec![&quotient_poly].into_iter(), Some(rng))?;
+    let quotient_poly_comm = quotient_poly_comm[0].clone();
+
+    let (z, opening_challenge) = zt_challenges::<F, PC>(diff_comm, &quotient_poly_comm)?;
+
+    let diff_eval = diff.evaluate(&z);
+    let quotient_eval = quotient_poly.evaluate(&z);
+
+    let opening_proof = PC::batch_open(
+        &ck,
+        vec![diff, &quotient_poly],
+        vec![diff_comm, &quotient_poly_comm],
+        &zt_query_set(z),
+        opening_challenge,
+        vec![diff_rand, &quotient_rand[0]],
+        Some(rng),
+    )?;
+    end_timer!(zt_time);
+
+    Ok(ZtProof {
+        quotient_poly_comm: quotient_poly_comm.commitment().clone(),
+        diff_eval,
+        quotient_eval,
+        opening_proof,
+    })
+}
 
-    // let witness: Vec<String> = serde_json::from_str(&witness).unwrap();
+// Multiplies the randomized entries of C by their poso_rand, both in the matrix the Marlin prover
+// reads and in the val evaluations over K, then re-interpolates val. An evaluation is the entry
+// times the inverse of eq_poly at its column, so scaling it in place scales the entry alone.
+// Only C may be randomized: the first Marlin round, which fixes poso_rand, reads A and B
+fn randomize_c<F: PrimeField>(
+    c: &mut [Vec<(F, usize)>],
+    val_c: &mut LabeledPolynomial<F>,
+    evals: &mut EvaluationsOnDomain<F>,
+    coefficients: &[IndexedCoeff],
+    poso_rand: &[u64]
+) {
+    for coeff in coefficients.iter().filter(|coeff| coeff.matrix == Matrix::C) {
+        let rand = F::from(poso_rand[coeff.slot]);
+        for entry in c[coeff.row].iter_mut().filter(|(_, col)| *col == coeff.col) {
+            entry.0 *= rand;
+        }
+        evals.evals[coeff.pos] *= rand;
+    }
 
-    // // convert witness into field elements
-    // let witness = witness
-    //     .iter()
-    //     .map(|w| {
-    //         BlsFr::from_str(&w).unwrap()
-    //     })
-    //     .collect::<Vec<BlsFr>>();
+    *val_c = LabeledPolynomial::new(val_c.label().clone(), evals.clone().interpolate(), None, None);
+}
 
-    // let witness = Some(witness);
+pub(crate) fn prove<E: PackCurve, PC: HomomorphicCommitment<E::Fr>, R: RngCore + CryptoRng>(
+    pack_pk: &ProverKey<E, PC>,
+    circuit: CircomCircuit<E>,
+    rng: &mut R
+) -> Result<PackMarlinProof<E, PC>, PackMarlinError> {
+    let pk = &pack_pk.index_pk;
+
+    let witness = circuit.witness.as_ref()
+        .ok_or_else(|| PackMarlinError::WitnessMismatch("no witness to prove".into()))?;
+    let public_input: Vec<E::Fr> = witness[1..circuit.r1cs.num_inputs].to_vec();
+
+    // The first Marlin round commits to the witness and to z_a, z_b, neither of which depends on
+    // poso_rand or on C. It is run here with a zk rng from a fresh seed, and Marlin::prove replays it
+    // from the same seed on the randomized index, so poso_rand is bound to the first-round
+    // commitments the verifier reads from the Marlin proof.
+    let first_round_time = start_timer!(|| "Committing to the first Marlin round");
+    let mut seed = [0u8; 32];
+    rng.fill_bytes(&mut seed);
+
+    let zk_rng = &mut ChaChaRng::from_seed(seed);
+    let prover_state = AHPForR1CS::prover_init(&pk.index, circuit.clone())?;
+    let (_, first_oracles, _) = AHPForR1CS::prover_first_round(prover_state, zk_rng)?;
+    let (first_comms, _) = PC::commit(&pk.committer_key, first_oracles.iter(), Some(zk_rng))?;
+    let first_comms = first_comms
+        .iter()
+        .map(|c| c.commitment().clone())
+        .collect::<Vec<PC::Commitment>>();
+    end_timer!(first_round_time);
 
-    // let gen_time = start_timer!(|| "Generating constraints");
-    // let mut circuit = CircomCircuit::<Bls12_381>{r1cs, witness};
-    // // let cs = ConstraintSystem::<BlsFr>::new_ref();
-    // circuit.r1cs.wire_mapping = None;
-    // // circuit.clone().generate_constraints(cs.clone()).unwrap();
-    // end_timer!(gen_time);
-    // end_timer!(file_time);
+    // compute poso_rand
+    let poso_time = start_timer!(|| "Computing poso_rand");
+    let poso_rand = poso_challenges(&pk.index_vk, &public_input, &first_comms, &pack_pk.params)?;
+    end_timer!(poso_time);
 
-    // update vk inside pk
-    // three things to update, c, val_c, and evals_on_K.val_c
+    // update vk inside pk: C, its val evaluations over K and val_c
 
     let update_time = start_timer!(|| "Updating vk inside pk");
     let mut mod_pk = pk.clone();
 
-    for i in 0..mod_pk.index.c[0].len() {
-        for j in 0..11 {
-            mod_pk.index.c[0][0].0 = mod_pk.index.c[0][0].0 + BlsFr::from(poso_rand[i*11 + j % poso_size*11]);
-        }
-    }
+    let index = &mut mod_pk.index;
+    let val_c = &mut index.joint_arith.val_c;
+    let old_val = val_c.polynomial().clone();
+    randomize_c(&mut index.c, val_c, &mut index.joint_arith.evals_on_K.val_c, &pack_pk.coefficients, &poso_rand);
+    let diff_poly = val_c.polynomial() - &old_val;
 
-    let c = mod_pk.index.c
-        .iter()
-        .enumerate()
-        .map(|(r, row)| row.iter().map(move |(f, i)| ((r, *i), *f)))
-        .flatten()
-        .collect::<BTreeMap<(usize, usize), BlsFr>>();
-    
-    let mut val_c_vec = Vec::with_capacity(domain_k.size());
-
-    for (r, row) in mod_pk.index.c.iter().enumerate() {
-        for i in row {
-            val_c_vec.push(c.get(&(r, i.1)).copied().unwrap_or(BlsFr::zero()));
-        }
-    }
-
-    // Needs to be multiplied by eq_poly_vals, but these are already precomputed and do not change
-    let val_c = EvaluationsOnDomain::from_vec_and_domain(val_c_vec, domain_k);
-    mod_pk.index.joint_arith.val_c = LabeledPolynomial::new("val_c".to_string(), val_c.clone().interpolate(), None, None);
-
-    mod_pk.index.joint_arith.evals_on_K.val_c = val_c.clone();
+    // val_c is committed without hiding, so its randomness in index_comm_rands stays valid
+    let (val_comm, _) = 
+        PC::commit(&pk.committer_key, vec![&*val_c], None)?;
+    mod_pk.index_vk.index_comms[val_index::<E::Fr>(Matrix::C)] = val_comm[0].commitment().clone();
 
-    // update the commitment to val_c - dummy MSM for now of size poso_size*reps
-    let poso_rand: Vec<BlsFr> = poso_rand
-        .iter()
-        .map(|w| {
-            BlsFr::from(*w)
-        })
-        .collect::<Vec<BlsFr>>();
-    let diff = poso_rand.clone();
-    
-    //commit to diff
+    // the verifier derives the commitment to the diff homomorphically from the location commitments
     let diff_time = start_timer!(|| "Committing to diff polynomial");
-    let diff_poly = DensePolynomial::from_coefficients_vec(diff);
-    let diff = LabeledPolynomial::new("diff".to_string(), diff_poly.clone(), None, None);
+    let diff = LabeledPolynomial::new("diff".to_string(), diff_poly, None, None);
     let diff_p = vec![&diff].into_iter();
-    let (_, _) = 
-        MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&mod_pk.committer_key, diff_p, None).unwrap();
+    let (diff_comm, diff_rand) = 
+        PC::commit(&mod_pk.committer_key, diff_p, None)?;
     end_timer!(diff_time);  
  
     end_timer!(update_time);
@@ -230,15 +263,28 @@ pub(crate) fn prove(
 
     // DONT run indexer again, run normal marlin prover and zerotest prover
     let proof = Marlin::<
-        BlsFr,
-        MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
+        E::Fr,
+        PC,
         SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-    >::prove(&pk, circuit.clone(), rng);
+    >::prove(&mod_pk, circuit, &mut ChaChaRng::from_seed(seed))?;
+
+    let replayed = proof.commitments.first().map(|comms| to_bytes![comms]).transpose()?;
+    if replayed != Some(to_bytes![first_comms]?) {
+        return Err(PackMarlinError::Marlin("the first Marlin round differs from the one poso_rand is bound to".into()));
+    }
+
+    // the val polynomials and so diff live on the domain K of the joint non-zeros
+    let domain_k = eval_domain::<E::Fr>(pk.index.index_info.num_non_zero)?;
+    let support = diff_support(&pack_pk.coefficients);
+    let zt_proof = zt_prover::<E::Fr, PC, _>(mod_pk.committer_key, domain_k, support, &diff, &diff_comm[0], &diff_rand[0], rng)?;
 
-    let zt_proof = zt_prover(mod_pk.committer_key, diff_poly);
+    // send proof consisting of the normal proof and zerotest proof
+    Ok(PackMarlinProof {
+        version: Version,
+        curve: CurveTag::new(),
+        diff_comm: diff_comm[0].commitment().clone(),
+        proof,
+        zt_proof,
+    })
 
-    // send proof consisting of 0th msg and normal proof and zerotest proof
-    // (zt_proof, proof.unwrap())
-    (zt_proof, proof.unwrap())
-    
 }
\ No newline at end of file
@@ -0,0 +1,298 @@
+use ark_ec_old::{AffineCurve, PairingEngine};
+use ark_ff::{BigInteger, Field, FpParameters, FromBytes, PrimeField, Zero};
+use ark_poly_commit::kzg10;
+use ark_std::{start_timer, end_timer};
+use rand::RngCore;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::curve::PackCurve;
+use crate::setup::check_tau_powers;
+use crate::error::PackMarlinError;
+
+// snarkjs .ptau layout: "ptau" | version u32 | number of sections u32, then sections of
+// type u32 | size u64 | data. Field elements are little-endian Montgomery, points uncompressed.
+const PTAU_MAGIC: &[u8; 4] = b"ptau";
+const HEADER_SECTION: u32 = 1;
+const TAU_G1_SECTION: u32 = 2;
+const TAU_G2_SECTION: u32 = 3;
+const ALPHA_TAU_G1_SECTION: u32 = 4;
+
+fn invalid(reason: &str) -> PackMarlinError {
+    PackMarlinError::InvalidSrs(format!(".ptau file: {}", reason))
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> Result<u32, PackMarlinError> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<u64, PackMarlinError> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+// Bytes of one base field element of `E`
+fn fq_bytes<E: PairingEngine>() -> usize {
+    <E::Fq as PrimeField>::BigInt::NUM_LIMBS * 8
+}
+
+// R^-1, which takes the Montgomery form x*R of a stored coordinate back to x.
+// Computed once per file rather than per coordinate
+fn montgomery_inverse<F: PrimeField>() -> Result<F, PackMarlinError> {
+    F::from_repr(F::Params::R)
+        .and_then(|r| r.inverse())
+        .ok_or_else(|| invalid("no Montgomery constant"))
+}
+
+fn read_fq<E: PairingEngine, R: Read>(reader: &mut R, r_inv: E::Fq) -> Result<E::Fq, PackMarlinError> {
+    let repr = <E::Fq as PrimeField>::BigInt::read(reader)?;
+    if repr >= <E::Fq as PrimeField>::Params::MODULUS {
+        return Err(invalid("coordinate is not reduced modulo q"));
+    }
+
+    // stored in Montgomery form, x*R, so divide the value read by R
+    let mont = E::Fq::from_repr(repr).ok_or_else(|| invalid("coordinate is not reduced modulo q"))?;
+    Ok(mont * r_inv)
+}
+
+fn read_g1<E: PackCurve, R: Read>(reader: &mut R, r_inv: E::Fq) -> Result<E::G1Affine, PackMarlinError> {
+    let x = read_fq::<E, _>(reader, r_inv)?;
+    let y = read_fq::<E, _>(reader, r_inv)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(E::G1Affine::zero());
+    }
+
+    E::g1_from_xy(x, y).ok_or_else(|| invalid("invalid G1 point"))
+}
+
+fn read_g2<E: PackCurve, R: Read>(reader: &mut R, r_inv: E::Fq) -> Result<E::G2Affine, PackMarlinError> {
+    let x = [read_fq::<E, _>(reader, r_inv)?, read_fq::<E, _>(reader, r_inv)?];
+    let y = [read_fq::<E, _>(reader, r_inv)?, read_fq::<E, _>(reader, r_inv)?];
+    if x.iter().chain(&y).all(|c| c.is_zero()) {
+        return Ok(E::G2Affine::zero());
+    }
+
+    E::g2_from_xy(x, y).ok_or_else(|| invalid("invalid G2 point"))
+}
+
+// Offset and size of every section, by section type. Circom's binary files (.ptau, .r1cs, .wtns)
+// share this layout and differ in their magic.
+pub(crate) fn read_sections<R: Read + Seek>(
+    reader: &mut R,
+    expected_magic: &[u8; 4]
+) -> Result<BTreeMap<u32, (u64, u64)>, PackMarlinError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != expected_magic {
+        return Err(PackMarlinError::Parse(format!("not a .{} file", String::from_utf8_lossy(expected_magic))));
+    }
+
+    let _version = read_u32(reader)?;
+    let num_sections = read_u32(reader)?;
+
+    let mut sections = BTreeMap::new();
+    for _ in 0..num_sections {
+        let section_type = read_u32(reader)?;
+        let size = read_u64(reader)?;
+        let offset = reader.stream_position()?;
+        sections.insert(section_type, (offset, size));
+        reader.seek(SeekFrom::Current(size as i64))?;
+    }
+
+    Ok(sections)
+}
+
+pub(crate) fn seek_section<R: Read + Seek>(
+    reader: &mut R,
+    sections: &BTreeMap<u32, (u64, u64)>,
+    section: u32
+) -> Result<u64, PackMarlinError> {
+    let (offset, size) = sections.get(&section)
+        .ok_or_else(|| PackMarlinError::Parse(format!("missing section {}", section)))?;
+    reader.seek(SeekFrom::Start(*offset))?;
+    Ok(*size)
+}
+
+/// Reads a powers-of-tau transcript (snarkjs .ptau) over `E` as a Marlin-KZG10 SRS,
+/// keeping powers up to `max_degree` if given. tau*G1 become the powers of G, alpha*tau*G1
+/// the powers of gamma*G and the first two tau*G2 are H and beta*H; the powers are
+/// checked to be consistent with a randomized pairing check.
+pub fn import_ptau<E: PackCurve, R: RngCore>(
+    path: &Path,
+    max_degree: Option<usize>,
+    rng: &mut R
+) -> Result<kzg10::UniversalParams<E>, PackMarlinError> {
+    let import_time = start_timer!(|| "Packmarlin::Import_Ptau");
+    let mut reader = BufReader::new(File::open(path)?);
+    let sections = read_sections(&mut reader, PTAU_MAGIC)?;
+
+    seek_section(&mut reader, &sections, HEADER_SECTION)?;
+    let n8 = read_u32(&mut reader)? as usize;
+    if n8 != fq_bytes::<E>() {
+        return Err(invalid(&format!("not a {} ceremony", E::NAME)));
+    }
+    let q = <E::Fq as PrimeField>::BigInt::read(&mut reader)?;
+    if q != <E::Fq as PrimeField>::Params::MODULUS {
+        return Err(invalid(&format!("not a {} ceremony", E::NAME)));
+    }
+    let power = read_u32(&mut reader)?;
+
+    // alpha*tau*G1 has 2^power entries, and KZG needs one more power of gamma*G than of G
+    let available = 1usize.checked_shl(power)
+        .and_then(|n| n.checked_sub(2))
+        .ok_or_else(|| invalid(&format!("unsupported ceremony power {}", power)))?;
+    let max_degree = max_degree.unwrap_or(available);
+    if max_degree > available {
+        return Err(PackMarlinError::SrsTooSmall { needed: max_degree, available });
+    }
+
+    let r_inv = montgomery_inverse::<E::Fq>()?;
+    let g1_bytes = 2 * fq_bytes::<E>() as u64;
+    let size = seek_section(&mut reader, &sections, TAU_G1_SECTION)?;
+    if size < (max_degree as u64 + 1) * g1_bytes {
+        return Err(invalid("truncated tau*G1 section"));
+    }
+    let powers_of_g = (0..=max_degree).map(|_| read_g1::<E, _>(&mut reader, r_inv)).collect::<Result<Vec<_>, _>>()?;
+
+    let size = seek_section(&mut reader, &sections, TAU_G2_SECTION)?;
+    if size < 2 * 2 * g1_bytes {
+        return Err(invalid("truncated tau*G2 section"));
+    }
+    let h = read_g2::<E, _>(&mut reader, r_inv)?;
+    let beta_h = read_g2::<E, _>(&mut reader, r_inv)?;
+
+    let size = seek_section(&mut reader, &sections, ALPHA_TAU_G1_SECTION)?;
+    if size < (max_degree as u64 + 2) * g1_bytes {
+        return Err(invalid("truncated alpha*tau*G1 section"));
+    }
+    let powers_of_gamma_g = (0..=max_degree + 1).map(|_| read_g1::<E, _>(&mut reader, r_inv)).collect::<Result<Vec<_>, _>>()?;
+
+    let check_time = start_timer!(|| "Checking pairing consistency of the powers");
+    if powers_of_g[0] != E::G1Affine::prime_subgroup_generator() || h != E::G2Affine::prime_subgroup_generator() {
+        return Err(invalid("tau^0 is not the generator"));
+    }
+    if !check_tau_powers::<E, _>(&powers_of_g, h, beta_h, rng) || !check_tau_powers::<E, _>(&powers_of_gamma_g, h, beta_h, rng) {
+        return Err(invalid("powers of tau are inconsistent"));
+    }
+    end_timer!(check_time);
+
+    end_timer!(import_time);
+
+    Ok(kzg10::UniversalParams {
+        powers_of_g,
+        powers_of_gamma_g: powers_of_gamma_g.into_iter().enumerate().collect(),
+        h,
+        beta_h,
+        neg_powers_of_h: BTreeMap::new(),
+        prepared_h: h.into(),
+        prepared_beta_h: beta_h.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::{Bls12_381, Fq, Fr, G1Affine, G2Affine};
+    use ark_ec_old::ProjectiveCurve;
+    use ark_ff::One;
+    use ark_std::{test_rng, UniformRand};
+
+    use crate::test_utils::{sections_file, temp_file};
+
+    // x*R mod q, the Montgomery form snarkjs writes
+    fn mont(x: Fq) -> Vec<u8> {
+        let r = Fq::from_repr(<Fq as PrimeField>::Params::R).unwrap();
+        (x * r).into_repr().to_bytes_le()
+    }
+
+    fn g1_bytes(p: G1Affine) -> Vec<u8> {
+        [mont(p.x), mont(p.y)].concat()
+    }
+
+    fn g2_bytes(p: G2Affine) -> Vec<u8> {
+        [mont(p.x.c0), mont(p.x.c1), mont(p.y.c0), mont(p.y.c1)].concat()
+    }
+
+    fn header(power: u32) -> Vec<u8> {
+        let mut header = (fq_bytes::<Bls12_381>() as u32).to_le_bytes().to_vec();
+        header.extend(<Fq as PrimeField>::Params::MODULUS.to_bytes_le());
+        header.extend(power.to_le_bytes());
+        header.extend(power.to_le_bytes());
+        header
+    }
+
+    // A ceremony of 2^power powers of tau, with tau_h in place of tau in G2
+    fn ptau_file(power: u32, tau: Fr, tau_h: Fr, alpha: Fr) -> Vec<u8> {
+        let n = 1usize << power;
+        let g = G1Affine::prime_subgroup_generator();
+        let h = G2Affine::prime_subgroup_generator();
+        let powers = |x: Fr, count: usize| (0..count)
+            .scan(Fr::one(), move |cur, _| {
+                let power = *cur;
+                *cur *= x;
+                Some(power)
+            })
+            .collect::<Vec<Fr>>();
+
+        let tau_g1: Vec<u8> = powers(tau, 2 * n - 1).into_iter().flat_map(|t| g1_bytes(g.mul(t.into_repr()).into_affine())).collect();
+        let tau_g2: Vec<u8> = powers(tau_h, n).into_iter().flat_map(|t| g2_bytes(h.mul(t.into_repr()).into_affine())).collect();
+        let alpha_tau_g1: Vec<u8> = powers(tau, n).into_iter().flat_map(|t| g1_bytes(g.mul((alpha * t).into_repr()).into_affine())).collect();
+
+        sections_file(PTAU_MAGIC, &[
+            (HEADER_SECTION, header(power)),
+            (TAU_G1_SECTION, tau_g1),
+            (TAU_G2_SECTION, tau_g2),
+            (ALPHA_TAU_G1_SECTION, alpha_tau_g1),
+        ])
+    }
+
+    #[test]
+    fn imports_powers_of_tau() {
+        let rng = &mut test_rng();
+        let (tau, alpha) = (Fr::rand(rng), Fr::rand(rng));
+        let path = temp_file("import.ptau", &ptau_file(2, tau, tau, alpha));
+
+        let srs = import_ptau::<Bls12_381, _>(&path, None, rng).unwrap();
+        let g = G1Affine::prime_subgroup_generator();
+
+        assert_eq!(srs.powers_of_g.len(), 3);
+        assert_eq!(srs.powers_of_g[2], g.mul((tau * tau).into_repr()).into_affine());
+        assert_eq!(srs.powers_of_gamma_g[&3], g.mul((alpha * tau * tau * tau).into_repr()).into_affine());
+        assert_eq!(srs.beta_h, G2Affine::prime_subgroup_generator().mul(tau.into_repr()).into_affine());
+
+        assert!(matches!(
+            import_ptau::<Bls12_381, _>(&path, Some(3), rng),
+            Err(PackMarlinError::SrsTooSmall { needed: 3, available: 2 })
+        ));
+    }
+
+    #[test]
+    fn rejects_inconsistent_powers() {
+        let rng = &mut test_rng();
+        let path = temp_file("inconsistent.ptau", &ptau_file(2, Fr::rand(rng), Fr::rand(rng), Fr::rand(rng)));
+
+        assert!(matches!(
+            import_ptau::<Bls12_381, _>(&path, None, rng),
+            Err(PackMarlinError::InvalidSrs(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_powers() {
+        let rng = &mut test_rng();
+        for power in [0, 64, 100] {
+            let file = sections_file(PTAU_MAGIC, &[(HEADER_SECTION, header(power))]);
+            let path = temp_file(&format!("power-{}.ptau", power), &file);
+
+            assert!(matches!(
+                import_ptau::<Bls12_381, _>(&path, None, rng),
+                Err(PackMarlinError::InvalidSrs(_))
+            ));
+        }
+    }
+}
@@ -1,50 +1,361 @@
-use ark_std::{start_timer, end_timer};
-
-use crate::Blake2s;
-use crate::SimpleHashFiatShamirRng;
-use crate::DensePolynomial;
-use crate::ChaChaRng;
-use crate::MarlinKZG10;
-use crate::Marlin;
-use crate::{Bls12_381, BlsFr};
+use ark_ec_old::{msm::{FixedBaseMSM, VariableBaseMSM}, AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{FftField, Field, One, PrimeField};
+use ark_marlin::ahp::AHPForR1CS;
+use ark_poly::{EvaluationDomain, GeneralEvaluationDomain};
+use ark_poly_commit::kzg10;
+use ark_std::{start_timer, end_timer, UniformRand};
+use rand::{CryptoRng, RngCore};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use zeroize::Zeroize;
+
+use crate::R1CS;
 use crate::{CanonicalSerialize, CanonicalDeserialize};
 use crate::UniversalSRS;
+use crate::commitment::HomomorphicCommitment;
+use crate::error::PackMarlinError;
+
+/// KZG10 parameters up to `max_degree`, as `kzg10::KZG10::setup` computes them, except that
+/// the trapdoor beta and its powers are zeroized before returning.
+/// `produce_g2_powers` adds the negative powers of h that Sonic-KZG10 enforces degree bounds with
+pub(crate) fn kzg_setup<E: PairingEngine, R: RngCore + CryptoRng>(
+    max_degree: usize,
+    produce_g2_powers: bool,
+    rng: &mut R
+) -> kzg10::UniversalParams<E> {
+    let mut beta = E::Fr::rand(rng);
+    let g = E::G1Projective::rand(rng);
+    let gamma_g = E::G1Projective::rand(rng);
+    let h = E::G2Projective::rand(rng);
+
+    let mut powers_of_beta = vec![E::Fr::one()];
+    let mut cur = beta;
+    for _ in 0..max_degree {
+        powers_of_beta.push(cur);
+        cur *= &beta;
+    }
+    cur.zeroize();
+
+    let window_size = FixedBaseMSM::get_mul_window_size(max_degree + 1);
+    let scalar_bits = E::Fr::size_in_bits();
+
+    let g_time = start_timer!(|| "Generating powers of G");
+    let g_table = FixedBaseMSM::get_window_table(scalar_bits, window_size, g);
+    let powers_of_g = FixedBaseMSM::multi_scalar_mul::<E::G1Projective>(scalar_bits, window_size, &g_table, &powers_of_beta);
+    end_timer!(g_time);
+
+    let gamma_g_time = start_timer!(|| "Generating powers of gamma * G");
+    let gamma_g_table = FixedBaseMSM::get_window_table(scalar_bits, window_size, gamma_g);
+    let mut powers_of_gamma_g = FixedBaseMSM::multi_scalar_mul::<E::G1Projective>(scalar_bits, window_size, &gamma_g_table, &powers_of_beta);
+    // one more power of gamma_g, to support up to max_degree queries
+    let mut last_gamma_g = *powers_of_gamma_g.last().unwrap();
+    last_gamma_g *= beta;
+    powers_of_gamma_g.push(last_gamma_g);
+    end_timer!(gamma_g_time);
+
+    let powers_of_g = E::G1Projective::batch_normalization_into_affine(&powers_of_g);
+    let powers_of_gamma_g = E::G1Projective::batch_normalization_into_affine(&powers_of_gamma_g)
+        .into_iter()
+        .enumerate()
+        .collect();
+
+    let neg_powers_of_h = if produce_g2_powers {
+        let neg_h_time = start_timer!(|| "Generating negative powers of h");
+        let mut beta_inv = beta.inverse().expect("beta is non-zero");
+        let mut neg_powers_of_beta = vec![E::Fr::one()];
+        let mut cur = beta_inv;
+        for _ in 0..max_degree {
+            neg_powers_of_beta.push(cur);
+            cur *= &beta_inv;
+        }
+        cur.zeroize();
+        beta_inv.zeroize();
+
+        let neg_h_table = FixedBaseMSM::get_window_table(scalar_bits, window_size, h);
+        let neg_powers_of_h = FixedBaseMSM::multi_scalar_mul::<E::G2Projective>(scalar_bits, window_size, &neg_h_table, &neg_powers_of_beta);
+        neg_powers_of_beta.zeroize();
+        end_timer!(neg_h_time);
+
+        E::G2Projective::batch_normalization_into_affine(&neg_powers_of_h)
+            .into_iter()
+            .enumerate()
+            .collect()
+    } else {
+        BTreeMap::new()
+    };
+
+    let h = h.into_affine();
+    let beta_h = h.mul(beta.into_repr()).into_affine();
+
+    powers_of_beta.zeroize();
+    beta.zeroize();
+
+    kzg10::UniversalParams {
+        powers_of_g,
+        powers_of_gamma_g,
+        h,
+        beta_h,
+        neg_powers_of_h,
+        prepared_h: h.into(),
+        prepared_beta_h: beta_h.into(),
+    }
+}
+
+// e(sum r_i p_i, tau H) = e(sum r_i p_{i+1}, H) for random r, i.e. every p_{i+1} = tau * p_i
+pub(crate) fn check_tau_powers<E: PairingEngine, R: RngCore>(
+    powers: &[E::G1Affine],
+    h: E::G2Affine,
+    tau_h: E::G2Affine,
+    rng: &mut R
+) -> bool {
+    if powers.is_empty() {
+        return false;
+    }
+
+    let scalars = (0..powers.len() - 1)
+        .map(|_| E::Fr::rand(rng).into_repr())
+        .collect::<Vec<_>>();
 
-#[allow(dead_code)]
-pub(crate) fn universal_setup() -> UniversalSRS<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>> {
-    let nc = 5000000;
-    let nv = 5000000;
-    let nz = 10000000;
+    let lhs = VariableBaseMSM::multi_scalar_mul(&powers[..powers.len() - 1], &scalars);
+    let rhs = VariableBaseMSM::multi_scalar_mul(&powers[1..], &scalars);
+
+    E::pairing(lhs, tau_h) == E::pairing(rhs, h)
+}
+
+/// Whether the powers of G and of gamma*G both step by the beta of beta*H
+pub(crate) fn check_srs<E: PairingEngine, R: RngCore>(srs: &kzg10::UniversalParams<E>, rng: &mut R) -> bool {
+    let powers_of_gamma_g = srs.powers_of_gamma_g.values().cloned().collect::<Vec<E::G1Affine>>();
+
+    check_tau_powers::<E, _>(&srs.powers_of_g, srs.h, srs.beta_h, rng)
+        && check_tau_powers::<E, _>(&powers_of_gamma_g, srs.h, srs.beta_h, rng)
+}
+
+/// Constraint, variable and non-zero counts of a Marlin index, after the indexer pads the
+/// public input to a power of two and squares the matrices with empty constraints or variables
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexSizes {
+    pub num_constraints: usize,
+    pub num_variables: usize,
+    pub num_non_zero: usize,
+}
+
+impl IndexSizes {
+    pub fn of_r1cs<E: PairingEngine>(r1cs: &R1CS<E>) -> Result<Self, PackMarlinError> {
+        let num_inputs = eval_domain::<E::Fr>(r1cs.num_inputs)?.size();
+        let num_variables = num_inputs + r1cs.num_aux;
+        let dim = num_variables.max(r1cs.constraints.len());
+
+        // every val polynomial is evaluated over the same domain K, the joint non-zeros of A, B and C,
+        // so each row counts the union of the wires its three linear combinations touch
+        let num_non_zero = r1cs.constraints
+            .iter()
+            .map(|(a, b, c)| a.iter().chain(b).chain(c).map(|(w, _)| *w).collect::<BTreeSet<usize>>().len())
+            .sum::<usize>();
+
+        Ok(IndexSizes {
+            num_constraints: dim,
+            num_variables: dim,
+            num_non_zero,
+        })
+    }
+
+    /// Sizes covering both `self` and `other`, for an SRS shared by several circuits
+    pub fn max(self, other: IndexSizes) -> IndexSizes {
+        IndexSizes {
+            num_constraints: self.num_constraints.max(other.num_constraints),
+            num_variables: self.num_variables.max(other.num_variables),
+            num_non_zero: self.num_non_zero.max(other.num_non_zero),
+        }
+    }
+
+    /// Every count grown by `margin`, e.g. 0.1 for 10% headroom
+    pub fn with_margin(self, margin: f64) -> IndexSizes {
+        let grow = |n: usize| (n as f64 * (1.0 + margin)).ceil() as usize;
+
+        IndexSizes {
+            num_constraints: grow(self.num_constraints),
+            num_variables: grow(self.num_variables),
+            num_non_zero: grow(self.num_non_zero),
+        }
+    }
+
+    /// Largest polynomial degree the Marlin prover over `F` commits to for these sizes
+    pub fn max_degree<F: PrimeField>(&self) -> Result<usize, PackMarlinError> {
+        Ok(AHPForR1CS::<F>::max_degree(self.num_constraints, self.num_variables, self.num_non_zero)?)
+    }
+}
 
+/// Prefix of `srs` supporting polynomials up to `max_degree`, as if it had been generated at that size.
+/// Marlin-KZG10 and Sonic-KZG10 take the degree-bound shifts from the top of the powers they are given,
+/// so an index built on the prefix only needs powers below `max_degree`, however large the original SRS was.
+pub(crate) fn trim_universal<E: PairingEngine>(
+    srs: &kzg10::UniversalParams<E>,
+    max_degree: usize
+) -> Result<kzg10::UniversalParams<E>, PackMarlinError> {
+    if max_degree >= srs.powers_of_g.len() {
+        return Err(PackMarlinError::SrsTooSmall { needed: max_degree, available: srs.powers_of_g.len().saturating_sub(1) });
+    }
+
+    Ok(kzg10::UniversalParams {
+        powers_of_g: srs.powers_of_g[..=max_degree].to_vec(),
+        powers_of_gamma_g: srs.powers_of_gamma_g.range(..=max_degree + 1).map(|(i, p)| (*i, *p)).collect(),
+        h: srs.h,
+        beta_h: srs.beta_h,
+        neg_powers_of_h: srs.neg_powers_of_h.range(..=max_degree).map(|(i, p)| (*i, *p)).collect(),
+        prepared_h: srs.prepared_h.clone(),
+        prepared_beta_h: srs.prepared_beta_h.clone(),
+    })
+}
+
+/// Universal SRS for Marlin circuits up to the given sizes, with any trapdoor drawn from `rng`.
+/// Pass `OsRng` or another `CryptoRng`; `ark_std::test_rng` has a public seed and is only for tests.
+pub(crate) fn universal_setup<F: PrimeField, PC: HomomorphicCommitment<F>, R: RngCore + CryptoRng>(
+    nc: usize,
+    nv: usize,
+    nz: usize,
+    rng: &mut R
+) -> Result<UniversalSRS<F,PC>, PackMarlinError> {
     let uni_setup_time = start_timer!(|| "Packmarlin::New_Setup");
-    let rng = &mut ark_std::test_rng();
-
-    let srs = Marlin::<
-        BlsFr,
-        MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
-        SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-    >::universal_setup(nc, nv, nz, rng)
-    .unwrap();
-
-    // Write srs 
-    let mut srs_bytes = vec![];
-    srs.serialize_uncompressed(&mut srs_bytes).unwrap();
-    std::fs::write("packed_srs.bin", srs_bytes).unwrap();
-    
+
+    let max_degree = AHPForR1CS::<F>::max_degree(nc, nv, nz)?;
+    let srs = PC::universal_setup(max_degree, rng)?;
+
     end_timer!(uni_setup_time);
 
-    srs
+    Ok(srs)
+}
+
+/// Evaluation domain of at least `size` elements
+pub(crate) fn eval_domain<F: FftField>(size: usize) -> Result<GeneralEvaluationDomain<F>, PackMarlinError> {
+    GeneralEvaluationDomain::new(size)
+        .ok_or_else(|| PackMarlinError::Marlin(format!("no evaluation domain of size {}", size)))
+}
+
+/// Commitments to the Lagrange basis of `domain` for a commitment linear in the coefficients
+/// over the bases `powers`: their inverse FFT. With a `cache` path they are read from there
+/// if the file holds the basis of exactly these powers, and computed and written there otherwise.
+pub(crate) fn lagrange_srs<G: AffineCurve>(
+    powers: &[G],
+    domain: GeneralEvaluationDomain<G::ScalarField>,
+    cache: Option<&Path>
+) -> Result<Vec<G>, PackMarlinError> {
+    let lagrange_time = start_timer!(|| "Packmarlin::Lagrange_SRS");
+
+    if powers.len() < domain.size() {
+        return Err(PackMarlinError::SrsTooSmall { needed: domain.size() - 1, available: powers.len().saturating_sub(1) });
+    }
+    let powers = &powers[..domain.size()];
+
+    let cached = cache
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|bytes| Vec::<G>::deserialize_uncompressed(&bytes[..]).ok())
+        .filter(|lagrange| is_lagrange_basis(powers, lagrange, domain));
+    if let Some(lagrange) = cached {
+        end_timer!(lagrange_time);
+        return Ok(lagrange);
+    }
+
+    let powers_proj = powers
+        .iter()
+        .map(|p| p.into_projective())
+        .collect::<Vec<G::Projective>>();
+    let lagrange = domain.ifft(&powers_proj);
+    let lagrange = G::Projective::batch_normalization_into_affine(&lagrange);
+
+    if let Some(path) = cache {
+        let mut lagrange_bytes = vec![];
+        lagrange.serialize_uncompressed(&mut lagrange_bytes)?;
+        std::fs::write(path, lagrange_bytes)?;
+    }
+
+    end_timer!(lagrange_time);
+
+    Ok(lagrange)
 }
 
-#[allow(dead_code)]
-pub(crate) fn load_srs() -> UniversalSRS<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>> {
-    
-    let load_existing_setup_time = start_timer!(|| "Packmarlin::Load_Existing_Setup");
-    let srs_bytes = std::fs::read("packed_srs.bin").unwrap();
-    
-    let srs = 
-        UniversalSRS::<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>::deserialize_unchecked(&srs_bytes[..]).unwrap();
-    end_timer!(load_existing_setup_time);
+// sum rho_i L_i = sum ifft(rho)_j powers_j for a random rho, since L = ifft(powers)
+// and the inverse FFT matrix is symmetric
+fn is_lagrange_basis<G: AffineCurve>(
+    powers: &[G],
+    lagrange: &[G],
+    domain: GeneralEvaluationDomain<G::ScalarField>
+) -> bool {
+    if lagrange.len() != domain.size() {
+        return false;
+    }
 
-    srs
-}
\ No newline at end of file
+    let mut rng = rand::thread_rng();
+    let rho = (0..domain.size())
+        .map(|_| G::ScalarField::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let coeffs = domain.ifft(&rho);
+
+    let to_repr = |scalars: &[G::ScalarField]| scalars.iter().map(|s| s.into_repr()).collect::<Vec<_>>();
+    VariableBaseMSM::multi_scalar_mul(lagrange, &to_repr(&rho))
+        == VariableBaseMSM::multi_scalar_mul(powers, &to_repr(&coeffs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::{Bls12_381, Fr, G1Affine, G1Projective};
+    use ark_ff::Zero;
+    use ark_std::test_rng;
+
+    use crate::test_utils::{square, temp_file};
+
+    #[test]
+    fn counts_the_joint_non_zeros() {
+        let sizes = IndexSizes::of_r1cs(&square::<Bls12_381>()).unwrap();
+
+        // row 0 touches {y, x}, row 1 {one, y, x}
+        assert_eq!(sizes.num_non_zero, 5);
+    }
+
+    #[test]
+    fn lagrange_cache_is_reused_only_for_its_powers() {
+        let rng = &mut test_rng();
+        let powers = kzg_setup::<Bls12_381, _>(7, false, rng).powers_of_g;
+        let domain = eval_domain::<Fr>(8).unwrap();
+        let path = temp_file("lagrange.bin", &[]);
+
+        let lagrange = lagrange_srs(&powers, domain, None).unwrap();
+        // the Lagrange basis sums to 1
+        let sum = lagrange.iter().fold(G1Projective::zero(), |acc, p| acc + p.into_projective());
+        assert_eq!(sum.into_affine(), powers[0]);
+
+        assert_eq!(lagrange_srs(&powers, domain, Some(&path)).unwrap(), lagrange);
+        assert_eq!(lagrange_srs(&powers, domain, Some(&path)).unwrap(), lagrange);
+
+        // a cache of other powers is recomputed and overwritten
+        let other = kzg_setup::<Bls12_381, _>(7, false, rng).powers_of_g;
+        let other_lagrange = lagrange_srs(&other, domain, Some(&path)).unwrap();
+        assert_ne!(other_lagrange, lagrange);
+        assert_eq!(other_lagrange, lagrange_srs(&other, domain, None).unwrap());
+
+        assert!(matches!(
+            lagrange_srs::<G1Affine>(&powers[..4], domain, None),
+            Err(PackMarlinError::SrsTooSmall { needed: 7, available: 3 })
+        ));
+    }
+
+    #[test]
+    fn trims_to_a_prefix_and_rejects_srs_too_small() {
+        let rng = &mut test_rng();
+        let mut srs = kzg_setup::<Bls12_381, _>(8, false, rng);
+
+        let trimmed = trim_universal(&srs, 4).unwrap();
+        assert_eq!(trimmed.powers_of_g, srs.powers_of_g[..=4]);
+        assert_eq!(trimmed.powers_of_gamma_g.len(), 6);
+
+        assert!(matches!(
+            trim_universal(&srs, 9),
+            Err(PackMarlinError::SrsTooSmall { needed: 9, available: 8 })
+        ));
+        srs.powers_of_g.clear();
+        assert!(matches!(
+            trim_universal(&srs, 0),
+            Err(PackMarlinError::SrsTooSmall { needed: 0, available: 0 })
+        ));
+    }
+}
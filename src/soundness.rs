@@ -0,0 +1,55 @@
+use crate::params::PosoParams;
+use crate::error::PackMarlinError;
+
+// Repetition j checks every subcircuit constraint k with the combination sum_i r_ij * e_ik of the
+// error terms of all instances, with coefficients uniform in [1, 2^challenge_bits]. The errors are
+// committed before the challenges are drawn, so a cheating prover must pass every repetition for one
+// fixed constraint k with some e_ik non-zero. Once the other coefficients are fixed, that combination
+// vanishes for at most one value of r_ij, so with probability at most 2^-challenge_bits, and the
+// repetitions are independent. No union bound over k or the instances is needed.
+
+/// log2 of the soundness error of the poso check
+pub fn soundness_error_log2(params: &PosoParams) -> f64 {
+    -(params.challenge_bits as f64 * params.repetitions as f64)
+}
+
+/// Bits of security achieved by the poso check
+pub fn security_bits(params: &PosoParams) -> f64 {
+    -soundness_error_log2(params)
+}
+
+/// Fewest repetitions of `challenge_bits`-bit challenges reaching `target_bits` of security
+pub fn minimal_params(instances: usize, challenge_bits: u32, target_bits: u32) -> Result<PosoParams, PackMarlinError> {
+    let repetitions = (target_bits as f64 / challenge_bits as f64).ceil().max(1.0) as usize;
+
+    let params = PosoParams::new(instances, repetitions, challenge_bits)?;
+    debug_assert!(security_bits(&params) >= target_bits as f64);
+
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn security_grows_with_repetitions_only() {
+        let params = PosoParams::new(100, 4, 8).unwrap();
+
+        assert_eq!(security_bits(&params), 32.0);
+        assert_eq!(security_bits(&PosoParams::new(10000, 4, 8).unwrap()), 32.0);
+        assert_eq!(security_bits(&PosoParams::new(100, 5, 8).unwrap()), 40.0);
+    }
+
+    #[test]
+    fn minimal_params_reach_the_target_with_the_fewest_repetitions() {
+        let params = minimal_params(10000, 8, 80).unwrap();
+        assert_eq!(params.repetitions, 10);
+        assert!(security_bits(&params) >= 80.0);
+
+        let params = minimal_params(10000, 12, 80).unwrap();
+        assert_eq!(params.repetitions, 7);
+        let fewer = PosoParams::new(10000, params.repetitions - 1, 12).unwrap();
+        assert!(security_bits(&fewer) < 80.0);
+    }
+}
@@ -0,0 +1,215 @@
+use ark_ec_old::AffineCurve;
+use ark_ff::Zero;
+use ark_poly_commit::kzg10;
+use ark_std::{start_timer, end_timer, cfg_chunks};
+use memmap2::Mmap;
+use rand::RngCore;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use crate::{CanonicalDeserialize, CanonicalSerialize};
+use crate::curve::PackCurve;
+use crate::setup::check_srs;
+use crate::error::PackMarlinError;
+
+/// How much of an SRS file to check while loading it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SrsValidation {
+    /// Trust the file, e.g. one this machine generated
+    Unchecked,
+    /// Every point is on the curve and in the prime-order subgroup
+    Subgroup,
+    /// Subgroup checks plus a randomized pairing check that the powers are consistent
+    Full,
+}
+
+// Reads the files written by `serialize_uncompressed` of `kzg10::UniversalParams`:
+//   powers_of_g: Vec | powers_of_gamma_g: BTreeMap | h | beta_h | neg_powers_of_h: BTreeMap
+// with u64 lengths and keys and fixed-size uncompressed points.
+struct SrsBytes<'a> {
+    bytes: &'a [u8],
+    validation: SrsValidation,
+}
+
+impl<'a> SrsBytes<'a> {
+    fn take(&mut self, len: usize, what: &'static str) -> Result<&'a [u8], PackMarlinError> {
+        if self.bytes.len() < len {
+            return Err(PackMarlinError::InvalidSrs(format!("file ends before its {}", what)));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn length(&mut self, what: &'static str) -> Result<usize, PackMarlinError> {
+        Ok(u64::deserialize(self.take(8, what)?)? as usize)
+    }
+
+    fn point<G: AffineCurve>(
+        &self,
+        bytes: &[u8],
+        is_valid: fn(&G) -> bool,
+        section: &'static str,
+        index: usize
+    ) -> Result<G, PackMarlinError> {
+        // `deserialize_uncompressed` would skip the curve check, so both are done by `is_valid`
+        let p = G::deserialize_unchecked(bytes)?;
+        if self.validation != SrsValidation::Unchecked && !is_valid(&p) {
+            return Err(PackMarlinError::InvalidSrs(
+                format!("point {} of {} is not on the curve or not in the prime-order subgroup", index, section)
+            ));
+        }
+        Ok(p)
+    }
+
+    // The first `keep` of `count` points, each after an u64 key if `keyed`, with their keys
+    // (or positions if unkeyed)
+    fn points<G: AffineCurve>(
+        &mut self,
+        count: usize,
+        keep: usize,
+        keyed: bool,
+        is_valid: fn(&G) -> bool,
+        section: &'static str
+    ) -> Result<Vec<(usize, G)>, PackMarlinError> {
+        let key_size = if keyed { 8 } else { 0 };
+        let size = key_size + G::zero().uncompressed_size();
+        let entries = self.take(count.checked_mul(size).unwrap_or(usize::MAX), section)?;
+        let this = &*self;
+
+        cfg_chunks!(entries[..keep * size], size)
+            .enumerate()
+            .map(|(i, entry)| -> Result<_, PackMarlinError> {
+                let key = if keyed { u64::deserialize(&entry[..8])? as usize } else { i };
+                Ok((key, this.point(&entry[key_size..], is_valid, section, i)?))
+            })
+            .collect()
+    }
+}
+
+/// Memory-maps an SRS file written with `serialize_uncompressed` and reads the powers up to
+/// `max_degree` (all if `None`), checking them as `validation` asks.
+/// Only the pages holding the kept powers are read from disk.
+pub fn load_srs<E: PackCurve, R: RngCore>(
+    path: &Path,
+    max_degree: Option<usize>,
+    validation: SrsValidation,
+    rng: &mut R
+) -> Result<kzg10::UniversalParams<E>, PackMarlinError> {
+    let load_time = start_timer!(|| "Packmarlin::Load_SRS");
+
+    let file = File::open(path)?;
+    // the file must not change while mapped; SRS files are written once
+    let mmap = unsafe { Mmap::map(&file)? };
+    let mut cursor = SrsBytes { bytes: &mmap[..], validation };
+
+    let num_powers = cursor.length("powers of G")?;
+    if num_powers == 0 {
+        return Err(PackMarlinError::InvalidSrs("no powers of G".into()));
+    }
+    if let Some(needed) = max_degree.filter(|d| *d >= num_powers) {
+        return Err(PackMarlinError::SrsTooSmall { needed, available: num_powers.saturating_sub(1) });
+    }
+    let keep = max_degree.map_or(num_powers, |d| d + 1);
+    let powers_of_g = cursor.points(num_powers, keep, false, E::g1_is_valid, "powers of G")?
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect::<Vec<_>>();
+
+    // gamma*G has one power more than G; map entries are stored in key order
+    let num_gamma = cursor.length("powers of gamma*G")?;
+    let powers_of_gamma_g = cursor.points(num_gamma, (keep + 1).min(num_gamma), true, E::g1_is_valid, "powers of gamma*G")?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    let g2_size = E::G2Affine::zero().uncompressed_size();
+    let h_bytes = cursor.take(g2_size, "h")?;
+    let h = cursor.point(h_bytes, E::g2_is_valid, "h", 0)?;
+    let beta_h_bytes = cursor.take(g2_size, "beta*h")?;
+    let beta_h = cursor.point(beta_h_bytes, E::g2_is_valid, "beta*h", 0)?;
+
+    let num_neg = cursor.length("negative powers of h")?;
+    let neg_powers_of_h = cursor.points(num_neg, keep.min(num_neg), true, E::g2_is_valid, "negative powers of h")?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    let srs = kzg10::UniversalParams {
+        powers_of_g,
+        powers_of_gamma_g,
+        h,
+        beta_h,
+        neg_powers_of_h,
+        prepared_h: h.into(),
+        prepared_beta_h: beta_h.into(),
+    };
+
+    if validation == SrsValidation::Full && !check_srs(&srs, rng) {
+        return Err(PackMarlinError::InvalidSrs("powers are not consistent powers of the trapdoor".into()));
+    }
+
+    end_timer!(load_time);
+
+    Ok(srs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::Bls12_381;
+    use ark_std::test_rng;
+    use std::path::PathBuf;
+
+    use crate::setup::kzg_setup;
+    use crate::test_utils::temp_file;
+
+    fn srs_file(name: &str, srs: &kzg10::UniversalParams<Bls12_381>) -> PathBuf {
+        let mut bytes = vec![];
+        srs.serialize_uncompressed(&mut bytes).unwrap();
+        temp_file(name, &bytes)
+    }
+
+    #[test]
+    fn loads_what_setup_wrote() {
+        let rng = &mut test_rng();
+        let srs = kzg_setup::<Bls12_381, _>(8, true, rng);
+        let path = srs_file("setup.srs", &srs);
+
+        let loaded = load_srs::<Bls12_381, _>(&path, None, SrsValidation::Full, rng).unwrap();
+        assert_eq!(loaded.powers_of_g, srs.powers_of_g);
+        assert_eq!(loaded.powers_of_gamma_g, srs.powers_of_gamma_g);
+        assert_eq!((loaded.h, loaded.beta_h), (srs.h, srs.beta_h));
+        assert_eq!(loaded.neg_powers_of_h, srs.neg_powers_of_h);
+
+        let trimmed = load_srs::<Bls12_381, _>(&path, Some(4), SrsValidation::Full, rng).unwrap();
+        assert_eq!(trimmed.powers_of_g, srs.powers_of_g[..=4]);
+        assert_eq!(trimmed.powers_of_gamma_g.len(), 6);
+        assert_eq!(trimmed.neg_powers_of_h.len(), 5);
+
+        assert!(matches!(
+            load_srs::<Bls12_381, _>(&path, Some(9), SrsValidation::Unchecked, rng),
+            Err(PackMarlinError::SrsTooSmall { needed: 9, available: 8 })
+        ));
+    }
+
+    #[test]
+    fn rejects_inconsistent_or_empty_srs() {
+        let rng = &mut test_rng();
+        let mut srs = kzg_setup::<Bls12_381, _>(8, false, rng);
+        srs.beta_h = srs.h;
+        let path = srs_file("inconsistent.srs", &srs);
+
+        assert!(matches!(
+            load_srs::<Bls12_381, _>(&path, None, SrsValidation::Full, rng),
+            Err(PackMarlinError::InvalidSrs(_))
+        ));
+        // only a full check compares the powers
+        assert!(load_srs::<Bls12_381, _>(&path, None, SrsValidation::Subgroup, rng).is_ok());
+
+        let path = temp_file("empty.srs", &0u64.to_le_bytes());
+        assert!(matches!(
+            load_srs::<Bls12_381, _>(&path, None, SrsValidation::Full, rng),
+            Err(PackMarlinError::InvalidSrs(_))
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+use ark_ec_old::PairingEngine;
+use ark_ff::One;
+use std::path::PathBuf;
+
+use crate::R1CS;
+
+/// Circom binary file of the given (type, data) sections, as `ptau::read_sections` reads them
+pub(crate) fn sections_file(magic: &[u8; 4], sections: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend((sections.len() as u32).to_le_bytes());
+    for (section_type, data) in sections {
+        bytes.extend(section_type.to_le_bytes());
+        bytes.extend((data.len() as u64).to_le_bytes());
+        bytes.extend(data);
+    }
+
+    bytes
+}
+
+/// Writes `bytes` to a file in the temp directory, named uniquely for this test process
+pub(crate) fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("packmarlin-{}-{}", std::process::id(), name));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// x * x = y and (x + 1) * (x + 1) = y + 2x + 1 over the wires [one, y | x]
+pub(crate) fn square<E: PairingEngine>() -> R1CS<E> {
+    let one = E::Fr::one();
+
+    R1CS {
+        num_inputs: 2,
+        num_aux: 1,
+        num_variables: 3,
+        constraints: vec![
+            (vec![(2, one)], vec![(2, one)], vec![(1, one)]),
+            (vec![(0, one), (2, one)], vec![(0, one), (2, one)], vec![(0, one), (1, one), (2, one + one)]),
+        ],
+        wire_mapping: None,
+    }
+}
+
+/// Witness of `square` with the given x and y, which satisfies it iff y = x * x
+pub(crate) fn square_witness<E: PairingEngine>(x: u64, y: u64) -> Vec<E::Fr> {
+    vec![E::Fr::one(), E::Fr::from(y), E::Fr::from(x)]
+}
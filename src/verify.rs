@@ -1,90 +1,156 @@
-use ark_bls12_381_old::G1Affine;
-use ark_marlin::ahp::LabeledPolynomial;
-use ark_marlin::rng::FiatShamirRng;
-use ark_marlin::{ IndexVerifierKey, Proof};
-use ark_poly::{ UVPolynomial };
+use ark_ff::{One, PrimeField};
+use ark_marlin::IndexVerifierKey;
+use ark_poly::{EvaluationDomain, GeneralEvaluationDomain};
 use ark_poly_commit::{LabeledCommitment, PolynomialCommitment};
-use ark_poly_commit::marlin_pc::Commitment;
-use ark_poly_commit::marlin_pc::CommitterKey;
-use ark_std::{ start_timer, end_timer, cfg_into_iter };
-use rand::rngs::StdRng;
+use ark_std::{ start_timer, end_timer };
+use rand::RngCore;
+use std::collections::BTreeMap;
 use std::vec;
 
 use crate::Blake2s;
 use crate::SimpleHashFiatShamirRng;
-use crate::DensePolynomial;
 use crate::ChaChaRng;
-use crate::MarlinKZG10;
 use crate::Marlin;
-use crate::ark_ff::UniformRand;
-use crate::prove::{PROTOCOL_NAME, ZtProof};
-use crate::{ Bls12_381, BlsFr };
-
-fn commit_to_diff(poso_rand: Vec<BlsFr>, _loc_comm: Vec<G1Affine>, ck: CommitterKey<Bls12_381>) -> LabeledCommitment<Commitment<Bls12_381>> {
-    let diff_time = start_timer!(|| "Committing to diff polynomial");
-    
-    let diff = poso_rand.clone();
-
-    let diff = DensePolynomial::from_coefficients_vec(diff);
-    let diff = LabeledPolynomial::new("diff".to_string(), diff, None, None);
-    let diff_p = vec![&diff].into_iter();
-    let (diff_comm, _) = 
-        MarlinKZG10::<Bls12_381,DensePolynomial<BlsFr>>::commit(&ck.clone(), diff_p, None).unwrap();
-    
+use crate::curve::PackCurve;
+use crate::index::{val_index, LocComm, PackMarlinVerifierKey};
+use crate::layout::Matrix;
+use crate::prove::{ZtProof, PackMarlinProof, poso_challenges, support_points, zt_challenges, zt_query_set};
+use crate::setup::eval_domain;
+use crate::commitment::HomomorphicCommitment;
+use crate::error::PackMarlinError;
+
+// Commitment to val' - val of one matrix, an MSM of the coefficient shifts against its location commitments.
+// This is the homomorphic counterpart of the prover's commit, so no committer key is needed
+fn combine_loc_comm<F: PrimeField, PC: HomomorphicCommitment<F>>(
+    poso_shift: &[F],
+    loc_comm: &[LocComm<F, PC>],
+    matrix: Matrix
+) -> Result<PC::Commitment, PackMarlinError> {
+    let (bases, scalars): (Vec<PC::Commitment>, Vec<F>) = loc_comm
+        .iter()
+        .filter(|loc| loc.matrix == matrix)
+        .map(|loc| {
+            let shift = poso_shift.get(loc.slot).ok_or_else(|| PackMarlinError::LayoutMismatch(
+                format!("location commitment for slot {} of {}", loc.slot, poso_shift.len())
+            ))?;
+            Ok((loc.comm.clone(), *shift))
+        })
+        .collect::<Result<Vec<_>, PackMarlinError>>()?
+        .into_iter()
+        .unzip();
+
+    Ok(PC::combine(&bases, &scalars))
+}
+
+// Adds the per-matrix diff commitments to the val commitments of the original index,
+// returns the updated key and the commitment to the combined diff
+fn update_vk<F: PrimeField, PC: HomomorphicCommitment<F>>(
+    vk: &IndexVerifierKey<F,PC>,
+    poso_shift: &[F],
+    loc_comm: &[LocComm<F, PC>]
+) -> Result<(IndexVerifierKey<F,PC>, LabeledCommitment<PC::Commitment>), PackMarlinError> {
+    let diff_time = start_timer!(|| "MSM of poso_rand against location commitments");
+    let mut mod_vk = vk.clone();
+    let mut matrix_diffs = Vec::with_capacity(Matrix::ALL.len());
+
+    for matrix in Matrix::ALL {
+        let matrix_diff = combine_loc_comm(poso_shift, loc_comm, matrix)?;
+
+        let val_comm = &mut mod_vk.index_comms[val_index::<F>(matrix)];
+        *val_comm = PC::combine(&[val_comm.clone(), matrix_diff.clone()], &[F::one(), F::one()]);
+        matrix_diffs.push(matrix_diff);
+    }
+
+    let diff_comm = PC::combine(&matrix_diffs, &vec![F::one(); matrix_diffs.len()]);
     end_timer!(diff_time);
 
-    diff_comm[0].clone()
+    Ok((mod_vk, LabeledCommitment::new("diff".to_string(), diff_comm, None)))
 }
 
-fn zt_verify(ztpf: ZtProof) -> bool {
+fn zt_verify<F: PrimeField, PC: HomomorphicCommitment<F>, R: RngCore>(
+    pc_vk: &PC::VerifierKey,
+    domain: GeneralEvaluationDomain<F>,
+    support: (usize, usize),
+    diff_comm: &LabeledCommitment<PC::Commitment>,
+    ztpf: &ZtProof<F, PC>,
+    rng: &mut R
+) -> Result<bool, PackMarlinError> {
     let zt_time = start_timer!(|| "Verifying zt proof");
 
-    let mut _fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(&to_bytes![&PROTOCOL_NAME, &ztpf.quotient_poly_comm].unwrap());
+    let quotient_poly_comm = LabeledCommitment::new("quotient_poly".to_string(), ztpf.quotient_poly_comm.clone(), None);
+    let (z, opening_challenge) = zt_challenges::<F, PC>(diff_comm, &quotient_poly_comm)?;
 
-    end_timer!(zt_time);
+    // diff(z) * Z_S(z) = q(z) * Z_K(z)
+    let support_eval = support_points(domain, support).fold(F::one(), |acc, point| acc * (z - point));
+    let vanishing_eval = domain.evaluate_vanishing_polynomial(z);
+    let identity_holds = ztpf.diff_eval * support_eval == ztpf.quotient_eval * vanishing_eval;
+
+    let mut evaluations = BTreeMap::new();
+    evaluations.insert(("diff".to_string(), z), ztpf.diff_eval);
+    evaluations.insert(("quotient_poly".to_string(), z), ztpf.quotient_eval);
 
-    let is_valid = true;
+    let opening_valid = PC::batch_check(
+        pc_vk,
+        vec![diff_comm, &quotient_poly_comm],
+        &zt_query_set(z),
+        &evaluations,
+        &ztpf.opening_proof,
+        opening_challenge,
+        rng,
+    ).unwrap_or(false);
 
-    is_valid
+    end_timer!(zt_time);
+
+    Ok(identity_holds && opening_valid)
 }
 
-pub(crate) fn verify(
-    vk: &IndexVerifierKey<BlsFr,MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>,
-    pubinp: Vec<BlsFr>,
-    proof: Proof<BlsFr, MarlinKZG10<Bls12_381,DensePolynomial<BlsFr>>>,
-    ztpf: ZtProof,
-    rng: &mut StdRng,
-    loc_comm: Vec<G1Affine>,
-    ck_trim: CommitterKey<Bls12_381>
-) -> bool {
+/// Verifies from the verifier key alone: the val commitments are moved with the location
+/// commitments and every opening check uses only the commitment key in `index_vk.verifier_key`.
+/// A proof that fails any check is `ProofRejected`
+pub(crate) fn verify<E: PackCurve, PC: HomomorphicCommitment<E::Fr>, R: RngCore>(
+    pack_vk: &PackMarlinVerifierKey<E, PC>,
+    pubinp: &[E::Fr],
+    proof: &PackMarlinProof<E, PC>,
+    rng: &mut R
+) -> Result<(), PackMarlinError> {
     let vtime = start_timer!(|| "Packmarlin: Verify");
+    let vk = &pack_vk.index_vk;
 
     let poso_time = start_timer!(|| "Computing poso_rand");
 
-    let mut fs_rng: SimpleHashFiatShamirRng<Blake2s,ChaChaRng> = FiatShamirRng::initialize(&to_bytes![&PROTOCOL_NAME, &vk, &pubinp].unwrap());
-    fs_rng.absorb(&proof.commitments[1]);
-
-    let poso_rand = cfg_into_iter!(0..10000*11)
-        .map(|_| u16::from(u8::rand(&mut fs_rng)) + 1)
-        .collect::<Vec<u16>>();
-
-    let poso_rand: Vec<BlsFr> = poso_rand
+    // poso_rand is bound to the first-round commitments, which the Marlin verifier checks below
+    let first_comms = proof.proof.commitments.first().ok_or(PackMarlinError::ProofRejected)?;
+    let poso_rand = poso_challenges(vk, pubinp, first_comms, &pack_vk.params)?;
+    // the location commitments hold the unrandomized val entries, which move by poso_rand - 1 times themselves
+    let poso_shift: Vec<E::Fr> = poso_rand
         .iter()
         .map(|w| {
-            BlsFr::from(*w)
+            E::Fr::from(*w - 1)
         })
-        .collect::<Vec<BlsFr>>();
+        .collect::<Vec<E::Fr>>();
 
     end_timer!(poso_time);
 
-    commit_to_diff(poso_rand, loc_comm, ck_trim);
+    let update_time = start_timer!(|| "Updating val commitments");
+    let (mod_vk, diff_comm) = update_vk(vk, &poso_shift, &pack_vk.loc_comm)?;
+    end_timer!(update_time);
+
+    // the bundled diff commitment must be the one derived from the location commitments;
+    // compared as bytes since commitments need not implement `PartialEq`
+    let diff_matches = to_bytes![diff_comm.commitment()]? == to_bytes![&proof.diff_comm]?;
 
     let is_valid = Marlin::<
-        BlsFr,
-        MarlinKZG10<Bls12_381, DensePolynomial<BlsFr>>,
+        E::Fr,
+        PC,
         SimpleHashFiatShamirRng<Blake2s, ChaChaRng>,
-    >::verify(&vk, &pubinp, &proof, rng);
+    >::verify(&mod_vk, pubinp, &proof.proof, rng)?;
+    let domain_k = eval_domain::<E::Fr>(vk.index_info.num_non_zero)?;
+    let zt_valid = zt_verify::<E::Fr, PC, _>(&vk.verifier_key, domain_k, pack_vk.diff_support, &diff_comm, &proof.zt_proof, rng)?;
 
     end_timer!(vtime);
-    zt_verify(ztpf) & is_valid.unwrap()
+    if !(diff_matches && zt_valid && is_valid) {
+        return Err(PackMarlinError::ProofRejected);
+    }
+
+    Ok(())
 }
\ No newline at end of file
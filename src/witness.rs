@@ -0,0 +1,220 @@
+use ark_ff::{BigInteger, FpParameters, FromBytes, PrimeField};
+use ark_std::{start_timer, end_timer};
+use num::{BigInt, BigUint};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::WitnessCalculator;
+use crate::ptau::{read_sections, read_u32, seek_section};
+use crate::error::PackMarlinError;
+
+// Circom .wtns layout: "wtns" | version u32 | number of sections u32, then sections of
+// type u32 | size u64 | data. The header holds the field size n8, the prime and the number
+// of wires; the values are n8-byte little-endian integers in normal (non-Montgomery) form.
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const HEADER_SECTION: u32 = 1;
+const WITNESS_SECTION: u32 = 2;
+
+fn parse_error(reason: &str) -> PackMarlinError {
+    PackMarlinError::Parse(format!("witness: {}", reason))
+}
+
+fn is_wtns(path: &Path) -> Result<bool, PackMarlinError> {
+    let mut magic = [0u8; 4];
+    Ok(File::open(path)?.read_exact(&mut magic).is_ok() && &magic == WTNS_MAGIC)
+}
+
+/// Reads the witness of one instance from a Circom binary `.wtns` file over the field `F`
+pub fn read_wtns<F: PrimeField>(path: &Path) -> Result<Vec<F>, PackMarlinError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let sections = read_sections(&mut reader, WTNS_MAGIC)?;
+
+    seek_section(&mut reader, &sections, HEADER_SECTION)?;
+    let fr_bytes = F::BigInt::NUM_LIMBS * 8;
+    let n8 = read_u32(&mut reader)? as usize;
+    if n8 != fr_bytes {
+        return Err(parse_error("witness over another field"));
+    }
+    let r = F::BigInt::read(&mut reader)?;
+    if r != F::Params::MODULUS {
+        return Err(parse_error("witness over another field"));
+    }
+    let num_wires = read_u32(&mut reader)? as usize;
+
+    let size = seek_section(&mut reader, &sections, WITNESS_SECTION)?;
+    if size != (num_wires * fr_bytes) as u64 {
+        return Err(parse_error("witness section does not match the header"));
+    }
+
+    (0..num_wires)
+        .map(|_| {
+            let repr = F::BigInt::read(&mut reader)?;
+            F::from_repr(repr).ok_or_else(|| parse_error("value is not reduced modulo r"))
+        })
+        .collect()
+}
+
+// `F::from_str` would silently reduce values past the modulus
+fn to_field<F: PrimeField>(value: &BigUint) -> Result<F, PackMarlinError> {
+    let modulus = BigUint::from_bytes_le(&F::Params::MODULUS.to_bytes_le());
+    if value >= &modulus {
+        return Err(parse_error("value is not reduced modulo r"));
+    }
+    Ok(F::from_le_bytes_mod_order(&value.to_bytes_le()))
+}
+
+/// Reads a JSON array of arrays of decimal strings, one array per instance, e.g. witnesses or
+/// public inputs. Values must be reduced modulo the prime of `F`
+pub fn read_json<F: PrimeField>(path: &Path) -> Result<Vec<Vec<F>>, PackMarlinError> {
+    let values = std::fs::read_to_string(path)?;
+    let values: Vec<Vec<String>> = serde_json::from_str(&values)?;
+
+    values
+        .iter()
+        .map(|v| {
+            v.iter()
+                .map(|w| to_field(&BigUint::from_str(w).map_err(|_| parse_error("value is not a decimal integer"))?))
+                .collect::<Result<Vec<F>, PackMarlinError>>()
+        })
+        .collect::<Result<Vec<Vec<F>>, PackMarlinError>>()
+}
+
+/// Reads instance witnesses from a Circom `.wtns` file (one instance) or a JSON array of
+/// arrays of decimal strings (one per instance), told apart by the file header.
+/// Every witness must have the `num_variables` wires of the subcircuit.
+pub fn read_witnesses<F: PrimeField>(path: &Path, num_variables: usize) -> Result<Vec<Vec<F>>, PackMarlinError> {
+    let read_time = start_timer!(|| "Packmarlin::Read_Witnesses");
+
+    let witnesses = if is_wtns(path)? {
+        vec![read_wtns(path)?]
+    } else {
+        read_json(path)?
+    };
+
+    for w in &witnesses {
+        check_num_variables(w.len(), num_variables)?;
+    }
+    end_timer!(read_time);
+
+    Ok(witnesses)
+}
+
+fn check_num_variables(len: usize, num_variables: usize) -> Result<(), PackMarlinError> {
+    if len != num_variables {
+        return Err(PackMarlinError::WitnessMismatch(format!("{} wires instead of {}", len, num_variables)));
+    }
+
+    Ok(())
+}
+
+// Circom input signals: a number or decimal string, or nested arrays of them for array signals
+fn flatten_signal(value: &Value, out: &mut Vec<BigInt>) -> Result<(), PackMarlinError> {
+    let not_integer = |_| PackMarlinError::Parse(format!("input signal {} is not an integer", value));
+
+    match value {
+        Value::Array(values) => values.iter().try_for_each(|v| flatten_signal(v, out))?,
+        Value::Number(n) => out.push(BigInt::from_str(&n.to_string()).map_err(not_integer)?),
+        Value::String(s) => out.push(BigInt::from_str(s).map_err(not_integer)?),
+        _ => return Err(PackMarlinError::Parse(format!("input signal {} is not a number", value))),
+    }
+
+    Ok(())
+}
+
+// Input signals of every instance: one Circom input object, or an array with one per instance
+fn read_inputs(path: &Path) -> Result<Vec<Vec<(String, Vec<BigInt>)>>, PackMarlinError> {
+    let inputs = std::fs::read_to_string(path)?;
+    let inputs: Value = serde_json::from_str(&inputs)?;
+
+    let instances = match inputs {
+        Value::Array(instances) => instances,
+        object => vec![object],
+    };
+
+    instances
+        .iter()
+        .map(|instance| -> Result<Vec<(String, Vec<BigInt>)>, PackMarlinError> {
+            instance
+                .as_object()
+                .ok_or_else(|| PackMarlinError::Parse("Circom inputs must be an object of signals".into()))?
+                .iter()
+                .map(|(name, value)| -> Result<(String, Vec<BigInt>), PackMarlinError> {
+                    let mut values = vec![];
+                    flatten_signal(value, &mut values)?;
+                    Ok((name.clone(), values))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Computes the full witness of every instance in `inputs` (a Circom input object or an array
+/// of them) with the Circom-compiled witness generator `wasm`, which must target the prime of `F`.
+/// Every witness must have the `num_variables` wires of the subcircuit.
+pub fn calculate_witnesses<F: PrimeField>(wasm: &Path, inputs: &Path, num_variables: usize) -> Result<Vec<Vec<F>>, PackMarlinError> {
+    let calculate_time = start_timer!(|| "Packmarlin::Calculate_Witnesses");
+
+    let mut calculator = WitnessCalculator::new(wasm)
+        .map_err(|e| PackMarlinError::WitnessGeneration(e.to_string()))?;
+    let witnesses = read_inputs(inputs)?
+        .into_iter()
+        .map(|signals| {
+            let witness = calculator.calculate_witness(signals, true)
+                .map_err(|e| PackMarlinError::WitnessGeneration(e.to_string()))?;
+            check_num_variables(witness.len(), num_variables)?;
+
+            witness
+                .iter()
+                .map(|w| to_field(&w.to_biguint().ok_or_else(|| parse_error("value is negative"))?))
+                .collect::<Result<Vec<F>, PackMarlinError>>()
+        })
+        .collect::<Result<Vec<Vec<F>>, PackMarlinError>>()?;
+
+    end_timer!(calculate_time);
+
+    Ok(witnesses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381_old::Fr as BlsFr;
+    use ark_bn254_old::Fr as BnFr;
+
+    use crate::test_utils::{sections_file, temp_file};
+
+    fn wtns_file<F: PrimeField>(values: &[F], num_wires: u32) -> Vec<u8> {
+        let mut header = ((F::BigInt::NUM_LIMBS * 8) as u32).to_le_bytes().to_vec();
+        header.extend(F::Params::MODULUS.to_bytes_le());
+        header.extend(num_wires.to_le_bytes());
+        let data: Vec<u8> = values.iter().flat_map(|v| v.into_repr().to_bytes_le()).collect();
+
+        sections_file(WTNS_MAGIC, &[(HEADER_SECTION, header), (WITNESS_SECTION, data)])
+    }
+
+    #[test]
+    fn reads_wtns() {
+        let values = [1u64, 9, 3].map(BlsFr::from);
+        let path = temp_file("square.wtns", &wtns_file(&values, 3));
+
+        assert!(is_wtns(&path).unwrap());
+        assert_eq!(read_wtns::<BlsFr>(&path).unwrap(), values);
+    }
+
+    #[test]
+    fn rejects_wtns_over_another_field() {
+        let path = temp_file("bls.wtns", &wtns_file(&[1u64, 9, 3].map(BlsFr::from), 3));
+
+        assert!(matches!(read_wtns::<BnFr>(&path), Err(PackMarlinError::Parse(_))));
+    }
+
+    #[test]
+    fn rejects_wtns_shorter_than_its_header() {
+        let path = temp_file("short.wtns", &wtns_file(&[1u64, 9, 3].map(BlsFr::from), 4));
+
+        assert!(matches!(read_wtns::<BlsFr>(&path), Err(PackMarlinError::Parse(_))));
+    }
+}
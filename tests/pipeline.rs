@@ -0,0 +1,124 @@
+use ark_bls12_381_old::Bls12_381;
+use ark_bn254_old::Bn254;
+use ark_circom::circom::R1CS;
+use ark_ff::{One, PrimeField};
+use ark_std::test_rng;
+
+use packmarlin::{HomomorphicCommitment, IpaPC, MarlinKZG, PackCurve, PackMarlin, PackMarlinError, PosoParams, SonicKZG};
+
+// x * x = y and (x + 1) * (x + 1) = y + 2x + 1 over the wires [one, y | x]
+fn square<E: PackCurve>() -> R1CS<E> {
+    let one = E::Fr::one();
+
+    R1CS {
+        num_inputs: 2,
+        num_aux: 1,
+        num_variables: 3,
+        constraints: vec![
+            (vec![(2, one)], vec![(2, one)], vec![(1, one)]),
+            (vec![(0, one), (2, one)], vec![(0, one), (2, one)], vec![(0, one), (1, one), (2, one + one)]),
+        ],
+        wire_mapping: None,
+    }
+}
+
+fn witness<F: PrimeField>(x: u64) -> Vec<F> {
+    vec![F::one(), F::from(x * x), F::from(x)]
+}
+
+fn prove_and_verify<E: PackCurve, PC: HomomorphicCommitment<E::Fr>>() -> Result<(), PackMarlinError> {
+    let rng = &mut test_rng();
+    let sub = square::<E>();
+    let params = PosoParams::new(2, 2, 8)?;
+
+    let sizes = PackMarlin::<E, PC>::index_sizes(&sub, &params)?;
+    let srs = PackMarlin::<E, PC>::setup(sizes.num_constraints, sizes.num_variables, sizes.num_non_zero, rng)?;
+    let (pk, vk) = PackMarlin::<E, PC>::index(&srs, &sub, params, None)?;
+
+    let witnesses = vec![witness(3), witness(5)];
+    let public = witnesses
+        .iter()
+        .map(|w| pk.public_inputs(w))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let proof = PackMarlin::<E, PC>::prove(&pk, &witnesses, rng)?;
+    PackMarlin::<E, PC>::verify(&vk, &public, &proof, rng)?;
+
+    let mut wrong = public.clone();
+    wrong[1][0] += E::Fr::one();
+    assert!(matches!(
+        PackMarlin::<E, PC>::verify(&vk, &wrong, &proof, rng),
+        Err(PackMarlinError::ProofRejected)
+    ));
+
+    // the same values grouped into other instances
+    let regrouped = vec![public.concat(), vec![]];
+    assert!(matches!(
+        PackMarlin::<E, PC>::verify(&vk, &regrouped, &proof, rng),
+        Err(PackMarlinError::WitnessMismatch(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn prove_and_verify_bls12_381() {
+    prove_and_verify::<Bls12_381, MarlinKZG<Bls12_381>>().unwrap();
+}
+
+#[test]
+fn prove_and_verify_bn254() {
+    prove_and_verify::<Bn254, MarlinKZG<Bn254>>().unwrap();
+}
+
+#[test]
+fn prove_and_verify_sonic_kzg() {
+    prove_and_verify::<Bls12_381, SonicKZG<Bls12_381>>().unwrap();
+}
+
+#[test]
+fn prove_and_verify_ipa_pc() {
+    prove_and_verify::<Bls12_381, IpaPC<Bls12_381>>().unwrap();
+}
+
+#[test]
+fn unsatisfied_witness_is_rejected() {
+    let rng = &mut test_rng();
+    let sub = square::<Bls12_381>();
+    let params = PosoParams::new(2, 1, 8).unwrap();
+
+    let sizes = PackMarlin::<Bls12_381>::index_sizes(&sub, &params).unwrap();
+    let srs = PackMarlin::<Bls12_381>::setup(sizes.num_constraints, sizes.num_variables, sizes.num_non_zero, rng).unwrap();
+    let (pk, _) = PackMarlin::<Bls12_381>::index(&srs, &sub, params, None).unwrap();
+
+    let mut bad = witness(5);
+    bad[1] += <Bls12_381 as ark_ec_old::PairingEngine>::Fr::one();
+    let witnesses = vec![witness(3), bad];
+
+    assert!(matches!(
+        PackMarlin::<Bls12_381>::prove(&pk, &witnesses, rng),
+        Err(PackMarlinError::Unsatisfied)
+    ));
+}
+
+#[test]
+fn errors_cancelling_across_instances_are_rejected() {
+    let rng = &mut test_rng();
+    let sub = square::<Bls12_381>();
+    let params = PosoParams::new(2, 2, 8).unwrap();
+
+    let sizes = PackMarlin::<Bls12_381>::index_sizes(&sub, &params).unwrap();
+    let srs = PackMarlin::<Bls12_381>::setup(sizes.num_constraints, sizes.num_variables, sizes.num_non_zero, rng).unwrap();
+    let (pk, _) = PackMarlin::<Bls12_381>::index(&srs, &sub, params, None).unwrap();
+
+    // y is off by +1 in one instance and by -1 in the other
+    let mut high = witness(3);
+    let mut low = witness(3);
+    high[1] += <Bls12_381 as ark_ec_old::PairingEngine>::Fr::one();
+    low[1] -= <Bls12_381 as ark_ec_old::PairingEngine>::Fr::one();
+
+    assert!(matches!(
+        PackMarlin::<Bls12_381>::prove(&pk, &[high, low], rng),
+        Err(PackMarlinError::Unsatisfied)
+    ));
+}